@@ -0,0 +1,78 @@
+// pipe graph loading shared by both parts
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+pub type Vertex = String;
+pub type Graph = HashMap<Vertex, Vec<Vertex>>;
+
+pub enum Warning {
+    // pipe declared from the first vertex but not from the second one
+    Asymmetric(Vertex, Vertex),
+    Duplicate(Vertex, Vertex),
+}
+
+// builds an undirected graph from pipes declared on either side, every
+// vertex gets an entry (an empty one for a line without pipes) and each
+// neighbor is listed once
+pub fn load_graph(reader: &mut BufRead) -> (Graph, Vec<Warning>) {
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut declared: Vec<(Vertex, Vertex)> = Vec::new();
+
+    for line in reader.lines() {
+        let line = line.expect("io error");
+        let tokens = line.split_whitespace().map(|token| token.trim()).collect::<Vec<_>>();
+        if tokens.is_empty() {
+            continue;
+        }
+
+        assert!(tokens.len() >= 2 && tokens[1] == "<->", "invalid line: {}", line);
+        let from = tokens[0];
+        vertices.push(from.to_string());
+        for to in tokens[2..].iter().map(|token| token.trim_right_matches(',')).filter(|token| !token.is_empty()) {
+            declared.push((from.to_string(), to.to_string()));
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let mut declaration_counts: HashMap<(&Vertex, &Vertex), usize> = HashMap::new();
+    for &(ref from, ref to) in &declared {
+        let count = declaration_counts.entry((from, to)).or_insert(0);
+        *count += 1;
+        if *count == 2 {
+            warnings.push(Warning::Duplicate(from.clone(), to.clone()));
+        }
+    }
+
+    let mut graph = vertices.into_iter().map(|vertex| (vertex, Vec::new())).collect::<Graph>();
+    for &(ref from, ref to) in &declared {
+        if declaration_counts[&(from, to)] == 0 {
+            continue;
+        }
+        if !declaration_counts.contains_key(&(to, from)) {
+            warnings.push(Warning::Asymmetric(from.clone(), to.clone()));
+        }
+        declaration_counts.insert((from, to), 0);
+        declaration_counts.insert((to, from), 0);
+
+        graph.entry(from.clone()).or_insert(Vec::new()).push(to.clone());
+        if from != to {
+            graph.entry(to.clone()).or_insert(Vec::new()).push(from.clone());
+        }
+    }
+
+    (graph, warnings)
+}
+
+pub fn print_warnings(warnings: &Vec<Warning>) {
+    for warning in warnings {
+        match *warning {
+            Warning::Asymmetric(ref from, ref to) => {
+                eprintln!("warning: {} <-> {} is not declared by {}", from, to, to)
+            }
+            Warning::Duplicate(ref from, ref to) => {
+                eprintln!("warning: {} <-> {} is declared more than once", from, to)
+            }
+        }
+    }
+}
@@ -1,49 +1,28 @@
-use std::collections::{HashMap, HashSet};
-use std::io::BufRead;
+mod graph;
 
-type Graph = HashMap<i32, Vec<i32>>;
+use std::collections::HashSet;
 
-fn load_graph(reader: &mut BufRead) -> Graph {
-    let mut graph: Graph = Graph::new();
+use graph::Graph;
 
-    for line in reader.lines() {
-        let line = line.expect("io error");
-        let tokens = line.split_whitespace().map(|token| token.trim()).collect::<Vec<_>>();
+fn count_reachable(graph: &Graph, source: &str) -> usize {
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut stack = vec![source];
+    visited.insert(source);
 
-        assert!(tokens[1] == "<->");
-        let from = tokens[0].parse::<i32>().expect("invalid source");
-        let mut to = tokens[2..]
-            .iter()
-            .map(|token| token.trim_right_matches(',').parse::<i32>().expect("invalid destination"))
-            .collect::<Vec<_>>();
-
-        let mut edges = graph.entry(from).or_insert(Vec::new());
-        edges.append(&mut to);
-    }
-
-    graph
-}
-
-fn count_reachable(graph: &Graph, source: i32) -> usize {
-    let mut visited: HashSet<i32> = HashSet::new();
-
-    fn visit_neighbors(graph: &Graph, visited: &mut HashSet<i32>, vertex: i32) {
-        visited.insert(vertex);
-
-        for neighbor in graph.get(&vertex).expect("missing vertex") {
-            if !visited.contains(neighbor) {
-                visit_neighbors(graph, visited, *neighbor);
+    while let Some(vertex) = stack.pop() {
+        for neighbor in graph.get(vertex).expect("missing vertex") {
+            if visited.insert(neighbor.as_str()) {
+                stack.push(neighbor);
             }
         }
-    };
-
-    visit_neighbors(graph, &mut visited, source);
+    }
 
     visited.len()
 }
 
 fn main() {
     let stdin = std::io::stdin();
-    let graph = load_graph(&mut stdin.lock());
-    println!("{:?}", count_reachable(&graph, 0));
+    let (graph, warnings) = graph::load_graph(&mut stdin.lock());
+    graph::print_warnings(&warnings);
+    println!("{:?}", count_reachable(&graph, "0"));
 }
@@ -1,75 +1,416 @@
-use std::collections::{HashMap, HashSet};
-use std::io::BufRead;
+mod graph;
 
-type Graph = HashMap<i32, Vec<i32>>;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 
-fn load_graph(reader: &mut BufRead) -> Graph {
-    let mut graph: Graph = Graph::new();
+use graph::{Graph, Vertex};
 
-    for line in reader.lines() {
-        let line = line.expect("io error");
-        let tokens = line.split_whitespace().map(|token| token.trim()).collect::<Vec<_>>();
+// numeric ids are ordered by value and ahead of any other names
+fn compare_vertices(a: &Vertex, b: &Vertex) -> Ordering {
+    match (a.parse::<i64>(), b.parse::<i64>()) {
+        (Ok(x), Ok(y)) => x.cmp(&y).then_with(|| a.cmp(b)),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
 
-        assert!(tokens[1] == "<->");
-        let from = tokens[0].parse::<i32>().expect("invalid source");
-        let mut to = tokens[2..]
-            .iter()
-            .map(|token| token.trim_right_matches(',').parse::<i32>().expect("invalid destination"))
-            .collect::<Vec<_>>();
+// disjoint-set forest with union by size and path halving
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
 
-        let mut edges = graph.entry(from).or_insert(Vec::new());
-        edges.append(&mut to);
+impl DisjointSet {
+    fn new(num_elements: usize) -> DisjointSet {
+        DisjointSet {
+            parent: (0..num_elements).collect(),
+            size: vec![1; num_elements],
+        }
     }
 
-    graph
-}
+    fn find(&mut self, mut element: usize) -> usize {
+        while self.parent[element] != element {
+            let grandparent = self.parent[self.parent[element]];
+            self.parent[element] = grandparent;
+            element = grandparent;
+        }
+        element
+    }
 
-fn count_subgraphs(graph: &Graph) -> usize {
-    let mut num_subgraphs = 0;
-    let mut unvisited: HashSet<i32> = get_all_vertices(graph);
+    fn union(&mut self, a: usize, b: usize) {
+        let mut root_a = self.find(a);
+        let mut root_b = self.find(b);
+        if root_a == root_b {
+            return;
+        }
+
+        if self.size[root_a] < self.size[root_b] {
+            std::mem::swap(&mut root_a, &mut root_b);
+        }
+        self.parent[root_b] = root_a;
+        self.size[root_a] += self.size[root_b];
+    }
 
-    while !unvisited.is_empty() {
-        num_subgraphs += 1;
-        let vertex = *unvisited.iter().next().unwrap();
-        unvisited = &unvisited - &get_reachable(graph, vertex);
+    fn set_size(&mut self, element: usize) -> usize {
+        let root = self.find(element);
+        self.size[root]
     }
+}
 
-    num_subgraphs
+struct Components {
+    vertices: Vec<Vertex>,
+    indices: HashMap<Vertex, usize>,
+    sets: DisjointSet,
+    num_components: usize,
 }
 
-fn get_all_vertices(graph: &Graph) -> HashSet<i32> {
-    let mut vertices = HashSet::new();
+impl Components {
+    fn new(graph: &Graph) -> Components {
+        let mut vertices = Vec::new();
+        let mut indices = HashMap::new();
+
+        {
+            let mut add_vertex = |vertex: &Vertex| {
+                if !indices.contains_key(vertex) {
+                    indices.insert(vertex.clone(), vertices.len());
+                    vertices.push(vertex.clone());
+                }
+            };
 
-    for (from, to) in graph {
-        vertices.insert(*from);
-        for v in to {
-            vertices.insert(*v);
+            for (from, to) in graph {
+                add_vertex(from);
+                for v in to {
+                    add_vertex(v);
+                }
+            }
+        }
+
+        let mut sets = DisjointSet::new(vertices.len());
+        let mut num_components = vertices.len();
+
+        for (from, to) in graph {
+            for v in to {
+                let (a, b) = (indices[from], indices[v]);
+                if sets.find(a) != sets.find(b) {
+                    sets.union(a, b);
+                    num_components -= 1;
+                }
+            }
+        }
+
+        Components {
+            vertices: vertices,
+            indices: indices,
+            sets: sets,
+            num_components: num_components,
         }
     }
 
-    vertices
+    fn count(&self) -> usize {
+        self.num_components
+    }
+
+    // returns sorted members of every component, ordered by their lowest vertex
+    fn list(&mut self) -> Vec<Vec<Vertex>> {
+        let mut members: HashMap<usize, Vec<Vertex>> = HashMap::new();
+
+        for index in 0..self.vertices.len() {
+            let root = self.sets.find(index);
+            members.entry(root).or_insert(Vec::new()).push(self.vertices[index].clone());
+        }
+
+        let mut components = members.into_iter().map(|(_, mut vertices)| {
+            vertices.sort_by(compare_vertices);
+            vertices
+        }).collect::<Vec<_>>();
+        components.sort_by(|a, b| compare_vertices(&a[0], &b[0]));
+
+        components
+    }
+
+    fn component_of(&mut self, vertex: &str) -> Option<Vec<Vertex>> {
+        let index = match self.indices.get(vertex) {
+            Some(index) => *index,
+            None => return None,
+        };
+        let root = self.sets.find(index);
+
+        let mut members = Vec::with_capacity(self.sets.set_size(index));
+        for other in 0..self.vertices.len() {
+            if self.sets.find(other) == root {
+                members.push(self.vertices[other].clone());
+            }
+        }
+        members.sort_by(compare_vertices);
+
+        Some(members)
+    }
 }
 
-fn get_reachable(graph: &Graph, source: i32) -> HashSet<i32> {
-    let mut visited: HashSet<i32> = HashSet::new();
+const UNVISITED: usize = std::usize::MAX;
 
-    fn visit_neighbors(graph: &Graph, visited: &mut HashSet<i32>, vertex: i32) {
-        visited.insert(vertex);
+// simple undirected view of a Graph, both directions of a pipe and any
+// repeated declarations collapse into a single edge
+struct Network {
+    vertices: Vec<Vertex>,
+    indices: HashMap<Vertex, usize>,
+    neighbors: Vec<Vec<usize>>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Network {
+    fn new(graph: &Graph) -> Network {
+        let mut vertices = graph.keys().cloned().collect::<Vec<_>>();
+        for to in graph.values() {
+            vertices.extend(to.iter().cloned());
+        }
+        vertices.sort_by(compare_vertices);
+        vertices.dedup();
 
-        for neighbor in graph.get(&vertex).expect("missing vertex") {
-            if !visited.contains(neighbor) {
-                visit_neighbors(graph, visited, *neighbor);
+        let indices = vertices.iter().enumerate().map(|(i, v)| (v.clone(), i)).collect::<HashMap<_, _>>();
+
+        let mut edges = Vec::new();
+        for (from, to) in graph {
+            for v in to {
+                let (a, b) = (indices[from], indices[v]);
+                if a != b {
+                    edges.push((std::cmp::min(a, b), std::cmp::max(a, b)));
+                }
             }
         }
-    };
+        edges.sort();
+        edges.dedup();
+
+        let mut neighbors = vec![Vec::new(); vertices.len()];
+        for &(a, b) in &edges {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+        }
+
+        Network {
+            vertices: vertices,
+            indices: indices,
+            neighbors: neighbors,
+            edges: edges,
+        }
+    }
+
+    fn index(&self, vertex: &str) -> usize {
+        *self.indices.get(vertex).expect("unknown vertex")
+    }
+
+    // breadth-first search returning distances and predecessors of every
+    // vertex, UNVISITED marks vertices outside the source's component
+    fn search(&self, source: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut distances = vec![UNVISITED; self.vertices.len()];
+        let mut predecessors = vec![UNVISITED; self.vertices.len()];
+        let mut queue = std::collections::VecDeque::new();
+
+        distances[source] = 0;
+        queue.push_back(source);
+
+        while let Some(vertex) = queue.pop_front() {
+            for &neighbor in &self.neighbors[vertex] {
+                if distances[neighbor] == UNVISITED {
+                    distances[neighbor] = distances[vertex] + 1;
+                    predecessors[neighbor] = vertex;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        (distances, predecessors)
+    }
 
-    visit_neighbors(graph, &mut visited, source);
+    fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<Vertex>> {
+        let (distances, predecessors) = self.search(self.index(from));
 
-    visited
+        let mut vertex = self.index(to);
+        if distances[vertex] == UNVISITED {
+            return None;
+        }
+
+        let mut path = vec![self.vertices[vertex].clone()];
+        while predecessors[vertex] != UNVISITED {
+            vertex = predecessors[vertex];
+            path.push(self.vertices[vertex].clone());
+        }
+        path.reverse();
+
+        Some(path)
+    }
+
+    // exact diameter, runs a search from every member of the component;
+    // vertices are indexed by their position in the component so the work
+    // doesn't depend on the size of the whole network
+    fn diameter(&self, component: &Vec<Vertex>) -> usize {
+        let members = component.iter().map(|v| self.index(v)).collect::<Vec<_>>();
+        let local_indices = members.iter().enumerate().map(|(i, v)| (*v, i)).collect::<HashMap<_, _>>();
+        let mut distances = vec![UNVISITED; members.len()];
+        let mut queue = std::collections::VecDeque::new();
+        let mut diameter = 0;
+
+        for source in 0..members.len() {
+            for distance in distances.iter_mut() {
+                *distance = UNVISITED;
+            }
+            distances[source] = 0;
+            queue.push_back(source);
+
+            while let Some(vertex) = queue.pop_front() {
+                diameter = std::cmp::max(diameter, distances[vertex]);
+                for neighbor in &self.neighbors[members[vertex]] {
+                    let neighbor = local_indices[neighbor];
+                    if distances[neighbor] == UNVISITED {
+                        distances[neighbor] = distances[vertex] + 1;
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        diameter
+    }
+
+    // Tarjan's low-link search, run iteratively to handle long chains;
+    // returns bridges and articulation points
+    fn find_cut_edges_and_vertices(&self) -> (Vec<(Vertex, Vertex)>, Vec<Vertex>) {
+        let num_vertices = self.vertices.len();
+        let mut discovered = vec![UNVISITED; num_vertices];
+        let mut low = vec![0; num_vertices];
+        let mut is_articulation = vec![false; num_vertices];
+        let mut bridges = Vec::new();
+        let mut time = 0;
+
+        for root in 0..num_vertices {
+            if discovered[root] != UNVISITED {
+                continue;
+            }
+
+            let mut root_children = 0;
+            // (vertex, parent, index of the next neighbor to visit)
+            let mut stack = vec![(root, UNVISITED, 0)];
+            discovered[root] = time;
+            low[root] = time;
+            time += 1;
+
+            while let Some(&(vertex, parent, next)) = stack.last() {
+                if next < self.neighbors[vertex].len() {
+                    stack.last_mut().unwrap().2 += 1;
+                    let neighbor = self.neighbors[vertex][next];
+
+                    if discovered[neighbor] == UNVISITED {
+                        discovered[neighbor] = time;
+                        low[neighbor] = time;
+                        time += 1;
+                        if vertex == root {
+                            root_children += 1;
+                        }
+                        stack.push((neighbor, vertex, 0));
+                    } else if neighbor != parent {
+                        low[vertex] = std::cmp::min(low[vertex], discovered[neighbor]);
+                    }
+                    continue;
+                }
+
+                stack.pop();
+                if parent == UNVISITED {
+                    continue;
+                }
+
+                low[parent] = std::cmp::min(low[parent], low[vertex]);
+                if low[vertex] > discovered[parent] {
+                    bridges.push((std::cmp::min(parent, vertex), std::cmp::max(parent, vertex)));
+                }
+                if parent != root && low[vertex] >= discovered[parent] {
+                    is_articulation[parent] = true;
+                }
+            }
+
+            if root_children > 1 {
+                is_articulation[root] = true;
+            }
+        }
+
+        bridges.sort();
+        let bridges = bridges.into_iter()
+            .map(|(a, b)| (self.vertices[a].clone(), self.vertices[b].clone()))
+            .collect();
+        let articulation_points = (0..num_vertices)
+            .filter(|i| is_articulation[*i])
+            .map(|i| self.vertices[i].clone())
+            .collect();
+
+        (bridges, articulation_points)
+    }
+
+    // vertices are filled with a color cycled from graphviz's set312 scheme
+    // according to their component
+    fn write_dot(&self, components: &Vec<Vec<Vertex>>) {
+        fn quote(vertex: &Vertex) -> String {
+            format!("\"{}\"", vertex.replace("\\", "\\\\").replace("\"", "\\\""))
+        }
+
+        println!("graph pipes {{");
+        println!("    node [style=filled, colorscheme=set312];");
+        for (i, component) in components.iter().enumerate() {
+            for v in component {
+                println!("    {} [fillcolor={}];", quote(v), i % 12 + 1);
+            }
+        }
+        for &(a, b) in &self.edges {
+            println!("    {} -- {};", quote(&self.vertices[a]), quote(&self.vertices[b]));
+        }
+        println!("}}");
+    }
+}
+
+fn print_component(vertices: &Vec<Vertex>) {
+    println!("{}: {}", vertices.len(), vertices.join(", "));
 }
 
 fn main() {
     let stdin = std::io::stdin();
-    let graph = load_graph(&mut stdin.lock());
-    println!("{:?}", count_subgraphs(&graph));
+    let (graph, warnings) = graph::load_graph(&mut stdin.lock());
+    graph::print_warnings(&warnings);
+    let mut components = Components::new(&graph);
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let vertex_arg = |i: usize| args.get(i).expect("missing vertex").as_str();
+
+    match args.get(0).map(|arg| arg.as_str()) {
+        None => println!("{:?}", components.count()),
+        Some("list") => {
+            for vertices in components.list() {
+                print_component(&vertices);
+            }
+        }
+        Some("component") => {
+            print_component(&components.component_of(vertex_arg(1)).expect("unknown vertex"));
+        }
+        Some("path") => {
+            match Network::new(&graph).shortest_path(vertex_arg(1), vertex_arg(2)) {
+                Some(path) => println!("{}: {}", path.len() - 1, path.join(" -> ")),
+                None => println!("no path"),
+            }
+        }
+        Some("diameters") => {
+            let network = Network::new(&graph);
+            for component in components.list() {
+                println!("{}: {}", component[0], network.diameter(&component));
+            }
+        }
+        Some("cuts") => {
+            let (bridges, articulation_points) = Network::new(&graph).find_cut_edges_and_vertices();
+            for (a, b) in bridges {
+                println!("bridge {} <-> {}", a, b);
+            }
+            for v in articulation_points {
+                println!("articulation {}", v);
+            }
+        }
+        Some("dot") => Network::new(&graph).write_dot(&components.list()),
+        Some(mode) => panic!("unknown mode: {}", mode),
+    }
 }
@@ -3,15 +3,22 @@ use std::io::BufRead;
 
 struct Firewall {
     layers: HashMap<u32, u32>,
+    start_positions: HashMap<u32, u32>,
 }
 
 impl Firewall {
     fn new() -> Firewall {
         Firewall {
             layers: HashMap::new(),
+            start_positions: HashMap::new(),
         }
     }
 
+    // the analytic formulas assume every scanner starts at the top
+    fn starts_at_top(&self) -> bool {
+        self.start_positions.values().all(|position| *position == 0)
+    }
+
     fn get_severity(&self) -> u32 {
         let mut severity = 0;
 
@@ -26,22 +33,208 @@ impl Firewall {
     }
 }
 
+struct Scanner {
+    range: u32,
+    position: u32,
+    moving_down: bool,
+}
+
+impl Scanner {
+    fn new(range: u32, position: u32) -> Scanner {
+        assert!(position < range, "scanner starts outside its range");
+        Scanner {
+            range: range,
+            position: position,
+            moving_down: position + 1 < range,
+        }
+    }
+
+    fn step(&mut self) {
+        if self.range == 1 {
+            return;
+        }
+
+        if self.moving_down {
+            self.position += 1;
+        } else {
+            self.position -= 1;
+        }
+
+        if self.position == 0 || self.position + 1 == self.range {
+            self.moving_down = !self.moving_down;
+        }
+    }
+}
+
+struct Catch {
+    time: u32,
+    depth: u32,
+    severity: u32,
+}
+
+// tracks scanner positions picosecond by picosecond, the packet enters
+// layer 0 after waiting for the given delay
+struct Simulation {
+    scanners: HashMap<u32, Scanner>,
+    num_layers: u32,
+    max_range: u32,
+    time: u32,
+    delay: u32,
+}
+
+impl Simulation {
+    fn new(firewall: &Firewall, delay: u32) -> Simulation {
+        let scanners = firewall.layers.iter()
+            .map(|(depth, range)| {
+                let position = *firewall.start_positions.get(depth).unwrap_or(&0);
+                (*depth, Scanner::new(*range, position))
+            })
+            .collect::<HashMap<_, _>>();
+
+        Simulation {
+            num_layers: scanners.keys().max().map_or(0, |depth| depth + 1),
+            max_range: scanners.values().map(|scanner| scanner.range).max().unwrap_or(1),
+            scanners: scanners,
+            time: 0,
+            delay: delay,
+        }
+    }
+
+    fn packet_layer(&self) -> Option<u32> {
+        if self.time >= self.delay && self.time - self.delay < self.num_layers {
+            Some(self.time - self.delay)
+        } else {
+            None
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.time >= self.delay + self.num_layers
+    }
+
+    // moves the packet, reports whether it was caught and then moves the
+    // scanners, on_move sees the firewall right after each of both moves
+    fn step<F>(&mut self, on_move: &mut F) -> Option<Catch>
+        where F: FnMut(&Simulation)
+    {
+        let mut caught = None;
+
+        if let Some(depth) = self.packet_layer() {
+            if let Some(scanner) = self.scanners.get(&depth) {
+                if scanner.position == 0 {
+                    caught = Some(Catch {
+                        time: self.time,
+                        depth: depth,
+                        severity: depth * scanner.range,
+                    });
+                }
+            }
+        }
+        on_move(self);
+
+        for scanner in self.scanners.values_mut() {
+            scanner.step();
+        }
+        on_move(self);
+
+        self.time += 1;
+        caught
+    }
+
+    fn run<F>(&mut self, on_move: &mut F) -> Vec<Catch>
+        where F: FnMut(&Simulation)
+    {
+        let mut catches = Vec::new();
+        while !self.finished() {
+            if let Some(catch) = self.step(on_move) {
+                catches.push(catch);
+            }
+        }
+        catches
+    }
+
+    // draws the layers as in the puzzle statement, the packet is shown
+    // with parentheses in the top row
+    fn render(&self) -> String {
+        let packet_layer = self.packet_layer();
+        let header = (0..self.num_layers).map(|depth| format!("{:^3}", depth)).collect::<Vec<_>>();
+        let mut lines = vec![header.join(" ").trim_right().to_string()];
+
+        for row in 0..self.max_range {
+            let cells = (0..self.num_layers).map(|depth| {
+                let with_packet = row == 0 && packet_layer == Some(depth);
+                match self.scanners.get(&depth) {
+                    Some(scanner) if row < scanner.range => {
+                        let content = if scanner.position == row { 'S' } else { ' ' };
+                        if with_packet { format!("({})", content) } else { format!("[{}]", content) }
+                    }
+                    None if row == 0 => String::from(if with_packet { "(.)" } else { "..." }),
+                    _ => String::from("   "),
+                }
+            }).collect::<Vec<_>>();
+            lines.push(cells.join(" ").trim_right().to_string());
+        }
+
+        lines.join("\n")
+    }
+}
+
 fn load_firewall(reader: &mut std::io::BufRead) -> Firewall {
     let mut firewall = Firewall::new();
 
     for line in reader.lines() {
         let line = line.expect("io error");
-        let tokens = line.split(": ")
-            .map(|token| token.parse::<u32>().expect("invalid token"))
+        // layers may give a starting scanner position as "depth: range @ position"
+        let tokens = line.split(|ch| ch == ':' || ch == '@')
+            .map(|token| token.trim().parse::<u32>().expect("invalid token"))
             .collect::<Vec<_>>();
         firewall.layers.insert(tokens[0], tokens[1]);
+        if tokens.len() > 2 {
+            firewall.start_positions.insert(tokens[0], tokens[2]);
+        }
     }
 
     firewall
 }
 
+fn print_catches(catches: &Vec<Catch>) {
+    for catch in catches {
+        println!("caught in layer {} at picosecond {}, severity {}", catch.depth, catch.time, catch.severity);
+    }
+    println!("{}", catches.iter().map(|catch| catch.severity).sum::<u32>());
+}
+
 fn main() {
     let stdin = std::io::stdin();
     let firewall = load_firewall(&mut stdin.lock());
-    println!("{}", firewall.get_severity());
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let delay = args.get(1).map_or(0, |arg| arg.parse::<u32>().expect("invalid delay"));
+
+    match args.get(0).map(|arg| arg.as_str()) {
+        None => {
+            assert!(firewall.starts_at_top(), "starting positions require simulation");
+            println!("{}", firewall.get_severity());
+        }
+        Some("simulate") => print_catches(&Simulation::new(&firewall, delay).run(&mut |_| {})),
+        Some("animate") => {
+            let mut after_packet_move = true;
+            let catches = Simulation::new(&firewall, delay).run(&mut |simulation| {
+                if after_packet_move {
+                    println!("Picosecond {}:", simulation.time);
+                }
+                println!("{}\n", simulation.render());
+                after_packet_move = !after_packet_move;
+            });
+            print_catches(&catches);
+        }
+        Some("check") => {
+            assert!(firewall.starts_at_top(), "starting positions have no analytic severity");
+            let simulated = Simulation::new(&firewall, 0).run(&mut |_| {});
+            let simulated_severity = simulated.iter().map(|catch| catch.severity).sum::<u32>();
+            println!("analytic {}, simulated {}", firewall.get_severity(), simulated_severity);
+            assert_eq!(firewall.get_severity(), simulated_severity);
+        }
+        Some(mode) => panic!("unknown mode: {}", mode),
+    }
 }
@@ -1,6 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io::BufRead;
 
+// upper bound on the number of residues kept in the sieve wheel, layers
+// which don't fit are checked against each candidate instead
+const MAX_WHEEL_SIZE: u64 = 1 << 22;
+
 struct Firewall {
     layers: HashMap<u32, u32>,
 }
@@ -10,25 +14,113 @@ impl Firewall {
         Firewall { layers: HashMap::new() }
     }
 
-    fn get_safe_trip_length(&self) -> Option<u32> {
-        for delay in 0..u32::max_value() {
-            if !self.packet_caught(delay) {
-                return Some(delay);
-            }
-        }
+    fn get_safe_trip_length(&self) -> Option<u64> {
+        self.safe_delays().next()
+    }
 
-        None
+    fn safe_delays(&self) -> SafeDelays {
+        SafeDelays::new(&self.get_forbidden_residues())
     }
 
-    fn packet_caught(&self, delay: u32) -> bool {
+    // a layer catches the packet when delay + depth is a multiple of its
+    // scanner period, so it forbids one residue of the delay modulo period
+    fn get_forbidden_residues(&self) -> BTreeMap<u64, BTreeSet<u64>> {
+        let mut residues = BTreeMap::new();
+
         for (depth, range) in &self.layers {
-            let period = range * 2 - 2;
-            if (delay + depth) % period == 0 {
-                return true;
+            // a scanner with range 1 never leaves the top
+            let period = if *range > 1 { (*range as u64 - 1) * 2 } else { 1 };
+            let residue = (period - *depth as u64 % period) % period;
+            residues.entry(period).or_insert(BTreeSet::new()).insert(residue);
+        }
+
+        residues
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: u64, b: u64) -> Option<u64> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+// yields safe delays in increasing order, combining layer periods into a
+// wheel of residues allowed modulo their least common multiple
+struct SafeDelays {
+    modulus: u64,
+    allowed: Vec<u64>,
+    remaining: Vec<(u64, BTreeSet<u64>)>,
+    // all layers repeat after this delay, so if nothing was found below it
+    // there are no safe delays at all
+    limit: u64,
+    found: bool,
+    base: u64,
+    index: usize,
+}
+
+impl SafeDelays {
+    fn new(residues: &BTreeMap<u64, BTreeSet<u64>>) -> SafeDelays {
+        let mut modulus = 1;
+        let mut allowed = vec![0];
+        let mut remaining = Vec::new();
+        let mut limit = Some(1);
+
+        for (&period, forbidden) in residues {
+            limit = limit.and_then(|limit| lcm(limit, period));
+
+            let new_modulus = lcm(modulus, period);
+            let wheel_size = new_modulus.and_then(|m| (m / modulus).checked_mul(allowed.len() as u64));
+            match (new_modulus, wheel_size) {
+                (Some(new_modulus), Some(wheel_size)) if wheel_size <= MAX_WHEEL_SIZE => {
+                    allowed = (0..new_modulus / modulus)
+                        .flat_map(|k| allowed.iter().map(move |r| k * modulus + r))
+                        .filter(|delay| !forbidden.contains(&(delay % period)))
+                        .collect();
+                    modulus = new_modulus;
+                }
+                _ => remaining.push((period, forbidden.clone())),
             }
         }
 
-        false
+        SafeDelays {
+            modulus: modulus,
+            allowed: allowed,
+            remaining: remaining,
+            limit: limit.unwrap_or(u64::max_value()),
+            found: false,
+            base: 0,
+            index: 0,
+        }
+    }
+}
+
+impl Iterator for SafeDelays {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.allowed.is_empty() {
+            return None;
+        }
+
+        loop {
+            if self.index == self.allowed.len() {
+                self.index = 0;
+                self.base = self.base.checked_add(self.modulus)?;
+            }
+            if self.base >= self.limit && !self.found {
+                return None;
+            }
+
+            let delay = self.base + self.allowed[self.index];
+            self.index += 1;
+
+            if self.remaining.iter().all(|&(period, ref forbidden)| !forbidden.contains(&(delay % period))) {
+                self.found = true;
+                return Some(delay);
+            }
+        }
     }
 }
 
@@ -49,6 +141,17 @@ fn load_firewall(reader: &mut std::io::BufRead) -> Firewall {
 fn main() {
     let stdin = std::io::stdin();
     let firewall = load_firewall(&mut stdin.lock());
-    println!("{}",
-             firewall.get_safe_trip_length().expect("no safe delay found"));
+
+    match std::env::args().nth(1) {
+        None => {
+            println!("{}",
+                     firewall.get_safe_trip_length().expect("no safe delay found"));
+        }
+        Some(bound) => {
+            let bound = bound.parse::<u64>().expect("invalid bound");
+            for delay in firewall.safe_delays().take_while(|delay| *delay < bound) {
+                println!("{}", delay);
+            }
+        }
+    }
 }
@@ -1,70 +1,216 @@
 #![feature(i128_type)]
 
+use std::io::Write;
+
+struct Config {
+    dimension: usize,
+    // "{prefix}" and "{row}" are substituted to form the key of each row,
+    // rows wider than a single hash need a "{chunk}" placeholder too
+    key_format: String,
+}
+
+impl Config {
+    fn new() -> Config {
+        Config {
+            dimension: 128,
+            key_format: String::from("{prefix}-{row}"),
+        }
+    }
+
+    fn key(&self, prefix: &str, row: usize, chunk: usize) -> String {
+        self.key_format
+            .replace("{prefix}", prefix)
+            .replace("{row}", &row.to_string())
+            .replace("{chunk}", &chunk.to_string())
+    }
+}
+
 struct Bitmap {
-    data: Vec<i32>
+    dimension: usize,
+    data: Vec<bool>,
+}
+
+struct Region {
+    size: usize,
+    min_row: usize,
+    min_col: usize,
+    max_row: usize,
+    max_col: usize,
+}
+
+// labels are 1-based region indices, 0 marks free squares
+struct Labeling {
+    dimension: usize,
+    labels: Vec<usize>,
+    regions: Vec<Region>,
 }
 
 impl Bitmap {
-    fn new(prefix: &str) -> Bitmap {
+    fn new(prefix: &str, config: &Config) -> Bitmap {
+        let dimension = config.dimension;
+        assert!(dimension <= 128 || config.key_format.contains("{chunk}"),
+                "key format needs a {} placeholder for rows wider than 128", "{chunk}");
+
         let mut bitmap = Bitmap {
-            data: vec![0; 128 * 128],
+            dimension: dimension,
+            data: vec![false; dimension * dimension],
         };
 
-        for row in 0..128 {
-            let generator = format!("{}-{}", prefix, row);
-            let mut hash = knot_hash(&generator.bytes().collect::<Vec<_>>());
-            for col in 0..128 {
-                bitmap.set_field(row, 127 - col, if hash & 1 != 0 { -1 } else { 0 });
-                hash >>= 1;
+        for row in 0..dimension {
+            for chunk in 0..(dimension + 127) / 128 {
+                let generator = config.key(prefix, row, chunk);
+                let hash = knot_hash(&generator.bytes().collect::<Vec<_>>());
+                let num_cols = std::cmp::min(128, dimension - chunk * 128);
+                for bit in 0..num_cols {
+                    bitmap.set_field(row, chunk * 128 + bit, hash & (1 << (127 - bit)) != 0);
+                }
             }
         }
 
         bitmap
     }
 
-    fn find_regions(&mut self) -> i32 {
-        let mut region_count: i32 = 0;
+    // flood fills every region with an explicit stack
+    fn label_regions(&self) -> Labeling {
+        let dimension = self.dimension;
+        let mut labels = vec![0; dimension * dimension];
+        let mut regions = Vec::new();
+        let mut stack = Vec::new();
+
+        for start in 0..dimension * dimension {
+            if !self.data[start] || labels[start] != 0 {
+                continue;
+            }
+
+            let label = regions.len() + 1;
+            let mut region = Region {
+                size: 0,
+                min_row: start / dimension,
+                min_col: start % dimension,
+                max_row: start / dimension,
+                max_col: start % dimension,
+            };
 
-        for row in 0..128 {
-            for col in 0..128 {
-                if self.field(row, col) >= 0 {
-                    continue;
+            labels[start] = label;
+            stack.push(start);
+
+            while let Some(index) = stack.pop() {
+                let (row, col) = (index / dimension, index % dimension);
+                region.size += 1;
+                region.min_row = std::cmp::min(region.min_row, row);
+                region.min_col = std::cmp::min(region.min_col, col);
+                region.max_row = std::cmp::max(region.max_row, row);
+                region.max_col = std::cmp::max(region.max_col, col);
+
+                let mut neighbors = Vec::with_capacity(4);
+                if row > 0 {
+                    neighbors.push(index - dimension);
+                }
+                if row + 1 < dimension {
+                    neighbors.push(index + dimension);
+                }
+                if col > 0 {
+                    neighbors.push(index - 1);
+                }
+                if col + 1 < dimension {
+                    neighbors.push(index + 1);
                 }
 
-                self.propagate_region(row, col, region_count + 1);
-                region_count += 1;
+                for neighbor in neighbors {
+                    if self.data[neighbor] && labels[neighbor] == 0 {
+                        labels[neighbor] = label;
+                        stack.push(neighbor);
+                    }
+                }
             }
+
+            regions.push(region);
         }
 
-        region_count
+        Labeling {
+            dimension: dimension,
+            labels: labels,
+            regions: regions,
+        }
     }
 
-    fn propagate_region(&mut self, row: i32, col: i32, region_index: i32) {
-        if self.field(row, col) >= 0 {
-            return;
-        }
+    fn field(&self, row: usize, col: usize) -> bool {
+        self.data[row * self.dimension + col]
+    }
 
-        self.set_field(row, col, region_index);
-        if row > 0 {
-            self.propagate_region(row - 1, col, region_index);
-        }
-        if row < 127 {
-            self.propagate_region(row + 1, col, region_index);
-        }
-        if col > 0 {
-            self.propagate_region(row, col - 1, region_index);
+    fn set_field(&mut self, row: usize, col: usize, value: bool) {
+        self.data[row * self.dimension + col] = value;
+    }
+
+    fn to_ascii(&self) -> String {
+        let mut output = String::new();
+        for row in 0..self.dimension {
+            for col in 0..self.dimension {
+                output.push(if self.field(row, col) { '#' } else { '.' });
+            }
+            output.push('\n');
         }
-        if col < 127 {
-            self.propagate_region(row, col + 1, region_index);
+        output
+    }
+
+    // binary PBM where used squares are black
+    fn to_pbm(&self) -> Vec<u8> {
+        let mut output = format!("P4\n{} {}\n", self.dimension, self.dimension).into_bytes();
+        for row in 0..self.dimension {
+            for byte_col in 0..(self.dimension + 7) / 8 {
+                let mut byte = 0u8;
+                for bit in 0..8 {
+                    let col = byte_col * 8 + bit;
+                    if col < self.dimension && self.field(row, col) {
+                        byte |= 0x80 >> bit;
+                    }
+                }
+                output.push(byte);
+            }
         }
+        output
     }
+}
 
-    fn field(&self, row: i32, col: i32) -> i32 {
-        *self.data.get((row * 128 + col) as usize).unwrap()
+impl Labeling {
+    fn label(&self, row: usize, col: usize) -> usize {
+        self.labels[row * self.dimension + col]
     }
 
-    fn set_field(&mut self, row: i32, col: i32, value: i32) {
-        *self.data.get_mut((row * 128 + col) as usize).unwrap() = value;
+    // regions are drawn with base 36 digits of their label, cycling past 'z'
+    fn to_ascii(&self) -> String {
+        let mut output = String::new();
+        for row in 0..self.dimension {
+            for col in 0..self.dimension {
+                let label = self.label(row, col);
+                output.push(if label == 0 {
+                    '.'
+                } else {
+                    std::char::from_digit(((label - 1) % 36) as u32, 36).unwrap()
+                });
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    // binary PPM with free squares in black and a color derived from the
+    // label for every region
+    fn to_ppm(&self) -> Vec<u8> {
+        let mut output = format!("P6\n{} {}\n255\n", self.dimension, self.dimension).into_bytes();
+        for &label in &self.labels {
+            if label == 0 {
+                output.extend_from_slice(&[0, 0, 0]);
+            } else {
+                let hash = (label as u32).wrapping_mul(2654435761);
+                output.extend_from_slice(&[
+                    (hash >> 24) as u8 | 0x40,
+                    (hash >> 16) as u8 | 0x40,
+                    (hash >> 8) as u8 | 0x40,
+                ]);
+            }
+        }
+        output
     }
 }
 
@@ -100,9 +246,41 @@ fn knot_hash(input: &Vec<u8>) -> u128 {
 }
 
 fn main() {
+    let mut config = Config::new();
+    let mut mode = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                config.dimension = args.next().expect("missing size").parse::<usize>().expect("invalid size");
+            }
+            "--key" => config.key_format = args.next().expect("missing key format"),
+            _ => mode = Some(arg),
+        }
+    }
+
     let mut line = String::new();
     std::io::stdin().read_line(&mut line).expect("io error");
     let input = line.trim();
-    let mut bitmap = Bitmap::new(input);
-    println!("{}", &mut bitmap.find_regions());
+    let bitmap = Bitmap::new(input, &config);
+    let labeling = bitmap.label_regions();
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    match mode.as_ref().map(|mode| mode.as_str()) {
+        None => println!("{}", labeling.regions.len()),
+        Some("regions") => {
+            for (i, region) in labeling.regions.iter().enumerate() {
+                println!("{}: {} squares, rows {}-{}, cols {}-{}",
+                         i + 1, region.size, region.min_row, region.max_row, region.min_col, region.max_col);
+            }
+        }
+        Some("ascii") => stdout.write_all(bitmap.to_ascii().as_bytes()).expect("io error"),
+        Some("ascii-regions") => stdout.write_all(labeling.to_ascii().as_bytes()).expect("io error"),
+        Some("pbm") => stdout.write_all(&bitmap.to_pbm()).expect("io error"),
+        Some("ppm") => stdout.write_all(&labeling.to_ppm()).expect("io error"),
+        Some(mode) => panic!("unknown mode: {}", mode),
+    }
 }
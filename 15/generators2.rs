@@ -2,6 +2,29 @@
 
 use std::io::BufRead;
 
+const MODULUS: u64 = 2147483647;
+
+// reduces a product of two values below MODULUS, relying on MODULUS being
+// the Mersenne prime 2^31 - 1 so that 2^31 is congruent to 1
+fn reduce(x: u64) -> u64 {
+    let x = (x & MODULUS) + (x >> 31);
+    let x = (x & MODULUS) + (x >> 31);
+    if x >= MODULUS { x - MODULUS } else { x }
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64) -> u64 {
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 != 0 {
+            result = reduce(result * base);
+        }
+        base = reduce(base * base);
+        exponent >>= 1;
+    }
+    result
+}
+
+#[derive(Clone)]
 struct Generator {
     value: u64,
     factor: u64,
@@ -9,13 +32,26 @@ struct Generator {
 }
 
 impl Generator {
+    // seed and factor only matter modulo MODULUS, reducing them keeps every
+    // product of two values within u64
     fn new(seed: u64, factor: u64, mask: u64) -> Generator {
         Generator {
-            value: seed,
-            factor: factor,
+            value: seed % MODULUS,
+            factor: factor % MODULUS,
             mask: mask,
         }
     }
+
+    // advances the raw sequence by the given number of steps in O(log n),
+    // ignoring the mask
+    fn jump(&mut self, steps: u64) {
+        self.value = reduce(self.value * pow_mod(self.factor, steps));
+    }
+
+    fn next_unfiltered(&mut self) -> u64 {
+        self.value = reduce(self.value * self.factor);
+        self.value
+    }
 }
 
 impl Iterator for Generator {
@@ -23,18 +59,61 @@ impl Iterator for Generator {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            self.value = (self.value * self.factor) % 2147483647;
-            if self.value & self.mask == 0 {
-                return Some(self.value);
+            let value = self.next_unfiltered();
+            if value & self.mask == 0 {
+                return Some(value);
             }
         }
     }
 }
 
-fn initialize_generators(reader: &mut BufRead) -> Vec<Generator> {
-    let factors = [16807, 48271];
-    let masks = [4 - 1, 8 - 1];
+struct Options {
+    factors: Vec<u64>,
+    masks: Vec<u64>,
+    num_pairs: Option<usize>,
+    num_threads: usize,
+    filtered: bool,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            factors: vec![16807, 48271],
+            masks: vec![4 - 1, 8 - 1],
+            num_pairs: None,
+            num_threads: 4,
+            filtered: true,
+        }
+    }
+
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Options {
+        fn parse_list(arg: Option<String>) -> Vec<u64> {
+            arg.expect("missing list")
+                .split(',')
+                .map(|value| value.parse::<u64>().expect("invalid list value"))
+                .collect()
+        }
+
+        let mut options = Options::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--factors" => options.factors = parse_list(args.next()),
+                "--masks" => options.masks = parse_list(args.next()),
+                "--pairs" => {
+                    options.num_pairs = Some(args.next().expect("missing count").parse().expect("invalid count"));
+                }
+                "--threads" => {
+                    options.num_threads = args.next().expect("missing count").parse().expect("invalid count");
+                }
+                "--unfiltered" => options.filtered = false,
+                _ => panic!("unknown option: {}", arg),
+            }
+        }
+        options
+    }
+}
 
+fn initialize_generators(reader: &mut BufRead, options: &Options) -> Vec<Generator> {
     reader.lines()
         .map(|line| {
             line.expect("io error")
@@ -44,24 +123,57 @@ fn initialize_generators(reader: &mut BufRead) -> Vec<Generator> {
                 .parse::<u64>()
                 .expect("invalid seed")
         })
-        .zip(factors.iter())
-        .zip(masks.iter())
+        .zip(options.factors.iter())
+        .zip(options.masks.iter())
         .map(|((seed, factor), mask)| Generator::new(seed, *factor, *mask))
         .collect::<Vec<_>>()
 }
 
-fn count_mismatches(num_inputs: usize, gen1: &mut Generator, gen2: &mut Generator) -> usize {
-    fn values_match(x: u64, y: u64) -> bool {
-        x & 0xffff == y & 0xffff
-    }
+fn values_match(x: u64, y: u64) -> bool {
+    x & 0xffff == y & 0xffff
+}
 
+fn count_mismatches(num_inputs: usize, gen1: &mut Generator, gen2: &mut Generator) -> usize {
     gen1.zip(gen2).take(num_inputs).filter(|values| values_match(values.0, values.1)).count()
 }
 
+// judges the unfiltered sequences, each thread jumps its own copy of the
+// generators to the start of its chunk of pairs
+fn count_unfiltered_mismatches(num_inputs: usize, num_threads: usize, gen1: &Generator, gen2: &Generator) -> usize {
+    let num_threads = std::cmp::max(num_threads, 1);
+    let chunk_size = (num_inputs + num_threads - 1) / num_threads;
+
+    let handles = (0..num_threads)
+        .map(|i| {
+            let start = std::cmp::min(i * chunk_size, num_inputs);
+            let end = std::cmp::min(start + chunk_size, num_inputs);
+            let mut gen1 = gen1.clone();
+            let mut gen2 = gen2.clone();
+
+            std::thread::spawn(move || {
+                gen1.jump(start as u64);
+                gen2.jump(start as u64);
+                (start..end)
+                    .filter(|_| values_match(gen1.next_unfiltered(), gen2.next_unfiltered()))
+                    .count()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    handles.into_iter().map(|handle| handle.join().expect("judge thread failed")).sum()
+}
+
 fn main() {
+    let options = Options::parse(std::env::args().skip(1));
     let stdin = std::io::stdin();
-    let mut generators = initialize_generators(&mut stdin.lock());
+    let mut generators = initialize_generators(&mut stdin.lock(), &options);
+
     if let [ref mut gen_a, ref mut gen_b] = generators[..] {
-        println!("{}", count_mismatches(5000000, gen_a, gen_b));
+        if options.filtered {
+            println!("{}", count_mismatches(options.num_pairs.unwrap_or(5000000), gen_a, gen_b));
+        } else {
+            let num_pairs = options.num_pairs.unwrap_or(40000000);
+            println!("{}", count_unfiltered_mismatches(num_pairs, options.num_threads, gen_a, gen_b));
+        }
     }
 }
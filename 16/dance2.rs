@@ -1,99 +1,354 @@
 #![feature(slice_patterns)]
-#![feature(slice_rotate)]
 
+use std::fmt;
+
+// positions are validated and partner labels resolved to alphabet indices
+// while parsing
 enum Move {
     Spin(usize),
     Exchange(usize, usize),
-    Partner(char, char),
+    Partner(usize, usize),
+    // reverses the order of programs at positions a through b inclusive
+    Reverse(usize, usize),
+    RotateLeft(usize),
+    Repeat(Vec<Move>, u64),
+}
+
+#[derive(Debug)]
+struct ParseError {
+    offset: usize,
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.message)
+    }
 }
 
-fn parse_moves(input: &str) -> Vec<Move> {
-    let mut moves = Vec::new();
-
-    for move_str in input.split(',').map(|token| token.trim()) {
-        let (kind, positions) = move_str.split_at(1);
-        moves.push(match kind {
-            "s" => Move::Spin(positions.parse::<usize>().expect("invalid spin")),
-            "x" => {
-                let mut programs = positions.split('/')
-                    .map(|token| token.parse::<usize>().expect("invalid program"));
-                Move::Exchange(programs.next().expect("missing first program"),
-                               programs.next().expect("missing second program"))
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+// moves are separated by commas, "(moves)*count" repeats a block and
+// blocks may be nested
+struct MoveParser<'a> {
+    input: &'a str,
+    offset: usize,
+    alphabet: &'a Vec<char>,
+}
+
+impl<'a> MoveParser<'a> {
+    fn new(input: &'a str, alphabet: &'a Vec<char>) -> MoveParser<'a> {
+        MoveParser {
+            input: input,
+            offset: 0,
+            alphabet: alphabet,
+        }
+    }
+
+    fn parse(&mut self) -> ParseResult<Vec<Move>> {
+        let moves = self.parse_list()?;
+        self.skip_whitespace();
+        if let Some(ch) = self.input[self.offset..].chars().next() {
+            return Err(self.error(self.offset, &format!("unexpected '{}'", ch)));
+        }
+        Ok(moves)
+    }
+
+    fn parse_list(&mut self) -> ParseResult<Vec<Move>> {
+        let mut moves = Vec::new();
+        loop {
+            moves.push(self.parse_item()?);
+            self.skip_whitespace();
+            if !self.accept(',') {
+                return Ok(moves);
             }
-            "p" => {
-                let position_chars = positions.chars().collect::<Vec<_>>();
-                if let [prog_a, '/', prog_b] = position_chars[..] {
-                    Move::Partner(prog_a, prog_b)
+        }
+    }
+
+    fn parse_item(&mut self) -> ParseResult<Move> {
+        self.skip_whitespace();
+        if !self.accept('(') {
+            return self.parse_move();
+        }
+
+        let start = self.offset - 1;
+        let block = self.parse_list()?;
+        self.skip_whitespace();
+        if !self.accept(')') {
+            return Err(self.error(start, "unterminated block"));
+        }
+        if !self.accept('*') {
+            return Err(self.error(self.offset, "expected '*' after block"));
+        }
+        let count_offset = self.offset;
+        let count = self.take_token(|ch| ch.is_digit(10));
+        let count = count.parse::<u64>().map_err(|_| self.error(count_offset, "invalid repeat count"))?;
+
+        Ok(Move::Repeat(block, count))
+    }
+
+    fn parse_move(&mut self) -> ParseResult<Move> {
+        let start = self.offset;
+        let token = self.take_token(|ch| !",()*".contains(ch) && !ch.is_whitespace());
+        if token.is_empty() {
+            return Err(self.error(start, "missing move"));
+        }
+
+        let first_len = token.chars().next().map_or(0, |ch| ch.len_utf8());
+        let (kind, args) = token.split_at(first_len);
+        match kind {
+            "s" => Ok(Move::Spin(self.parse_position(start + 1, args, true)?)),
+            "l" => Ok(Move::RotateLeft(self.parse_position(start + 1, args, true)?)),
+            "x" | "r" => {
+                let (a, b) = self.split_pair(start + 1, args)?;
+                let a = self.parse_position(start + 1, a, false)?;
+                let b = self.parse_position(start + 1, b, false)?;
+                if kind == "x" {
+                    Ok(Move::Exchange(a, b))
+                } else if a <= b {
+                    Ok(Move::Reverse(a, b))
                 } else {
-                    panic!("invalid program specification");
+                    Err(self.error(start, "reversed segment ends before it starts"))
                 }
             }
-            _ => {
-                panic!("Invalid move");
+            "p" => {
+                let label_chars = args.chars().collect::<Vec<_>>();
+                if let [prog_a, '/', prog_b] = label_chars[..] {
+                    Ok(Move::Partner(self.parse_label(start + 1, prog_a)?, self.parse_label(start + 3, prog_b)?))
+                } else {
+                    Err(self.error(start + 1, "invalid program specification"))
+                }
             }
-        });
+            _ => Err(self.error(start, &format!("unknown move '{}'", kind))),
+        }
     }
 
-    moves
-}
+    fn split_pair(&self, offset: usize, args: &'a str) -> ParseResult<(&'a str, &'a str)> {
+        let mut parts = args.splitn(2, '/');
+        match (parts.next(), parts.next()) {
+            (Some(a), Some(b)) => Ok((a, b)),
+            _ => Err(self.error(offset, "expected two positions separated by '/'")),
+        }
+    }
+
+    // counts of spins and rotations may span the whole line-up
+    fn parse_position(&self, offset: usize, token: &str, is_count: bool) -> ParseResult<usize> {
+        let position = token.parse::<usize>().map_err(|_| self.error(offset, &format!("invalid number '{}'", token)))?;
+        if position > self.alphabet.len() || (!is_count && position == self.alphabet.len()) {
+            return Err(self.error(offset, &format!("position {} out of range", position)));
+        }
+        Ok(position)
+    }
 
-fn apply_permutation_count(moves: &Vec<Move>, count: usize) -> Vec<char> {
-    let cycle_count = find_cycle(moves);
-    let remaining_count = count % cycle_count;
-    let mut programs = generate_initial_programs();
+    fn parse_label(&self, offset: usize, label: char) -> ParseResult<usize> {
+        self.alphabet.iter()
+            .position(|ch| *ch == label)
+            .ok_or(self.error(offset, &format!("unknown program '{}'", label)))
+    }
 
-    for _ in 0..remaining_count {
-        apply_permutation(&mut programs, moves);
+    fn take_token<F: Fn(char) -> bool>(&mut self, predicate: F) -> &'a str {
+        let rest = &self.input[self.offset..];
+        let len = rest.find(|ch| !predicate(ch)).unwrap_or(rest.len());
+        self.offset += len;
+        &rest[..len]
     }
 
-    programs
-}
+    fn skip_whitespace(&mut self) {
+        self.take_token(|ch| ch.is_whitespace());
+    }
 
-fn find_cycle(moves: &Vec<Move>) -> usize {
-    let initial = generate_initial_programs();
-    let mut permuted = initial.to_vec();
-    let mut counter = 0;
+    fn accept(&mut self, expected: char) -> bool {
+        if self.input[self.offset..].starts_with(expected) {
+            self.offset += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
 
-    loop {
-        apply_permutation(&mut permuted, moves);
-        counter += 1;
-        if permuted == initial {
-            return counter;
+    fn error(&self, offset: usize, message: &str) -> ParseError {
+        ParseError {
+            offset: offset,
+            message: String::from(message),
         }
     }
 }
 
-fn apply_permutation(programs: &mut Vec<char>, moves: &Vec<Move>) {
-    for m in moves.iter() {
-        match m {
-            &Move::Spin(count) => {
-                let count = programs.len() - count;
-                programs.rotate(count);
-            }
-            &Move::Exchange(a, b) => {
-                programs.swap(a, b);
+fn parse_moves(input: &str, alphabet: &Vec<char>) -> ParseResult<Vec<Move>> {
+    MoveParser::new(input, alphabet).parse()
+}
+
+// maps each slot of the result to the slot of the input it is taken from
+#[derive(Clone, PartialEq)]
+struct Permutation(Vec<usize>);
+
+impl Permutation {
+    fn identity(size: usize) -> Permutation {
+        Permutation((0..size).collect())
+    }
+
+    fn apply<T: Clone>(&self, items: &Vec<T>) -> Vec<T> {
+        self.0.iter().map(|i| items[*i].clone()).collect()
+    }
+
+    // permutation equivalent to applying self first and then other
+    fn then(&self, other: &Permutation) -> Permutation {
+        Permutation(other.apply(&self.0))
+    }
+
+    fn inverse(&self) -> Permutation {
+        let mut inverse = vec![0; self.0.len()];
+        for (i, source) in self.0.iter().enumerate() {
+            inverse[*source] = i;
+        }
+        Permutation(inverse)
+    }
+
+    fn pow(&self, mut exponent: u64) -> Permutation {
+        let mut result = Permutation::identity(self.0.len());
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 != 0 {
+                result = result.then(&base);
             }
-            &Move::Partner(a, b) => {
-                for p in programs.iter_mut() {
-                    if *p == a {
-                        *p = b;
-                    } else if *p == b {
-                        *p = a;
-                    }
+            base = base.then(&base);
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+// spins and exchanges only move programs between positions while partner
+// swaps only rename them, so a dance splits into two commuting
+// permutations: one over positions and one over labels
+#[derive(Clone)]
+struct Dance {
+    positions: Permutation,
+    labels: Permutation,
+}
+
+impl Dance {
+    fn identity(num_programs: usize) -> Dance {
+        Dance {
+            positions: Permutation::identity(num_programs),
+            labels: Permutation::identity(num_programs),
+        }
+    }
+
+    fn compile(moves: &Vec<Move>, num_programs: usize) -> Dance {
+        let mut dance = Dance::identity(num_programs);
+        let rotation = |count: usize| {
+            Permutation((0..num_programs).map(|i| (i + count) % num_programs).collect())
+        };
+
+        // labels.0[label] holds the label of the program now called label
+        // before the dance, so partner swaps simply exchange two entries
+        for m in moves.iter() {
+            match m {
+                &Move::Spin(count) => {
+                    dance.positions = dance.positions.then(&rotation(num_programs - count));
+                }
+                &Move::RotateLeft(count) => {
+                    dance.positions = dance.positions.then(&rotation(count));
+                }
+                &Move::Exchange(a, b) => {
+                    dance.positions.0.swap(a, b);
+                }
+                &Move::Reverse(a, b) => {
+                    dance.positions.0[a..b + 1].reverse();
+                }
+                &Move::Partner(a, b) => {
+                    dance.labels.0.swap(a, b);
+                }
+                &Move::Repeat(ref block, count) => {
+                    dance = dance.then(&Dance::compile(block, num_programs).pow(count));
                 }
             }
         }
+
+        dance
+    }
+
+    fn then(&self, other: &Dance) -> Dance {
+        Dance {
+            positions: self.positions.then(&other.positions),
+            labels: self.labels.then(&other.labels),
+        }
+    }
+
+    fn inverse(&self) -> Dance {
+        Dance {
+            positions: self.positions.inverse(),
+            labels: self.labels.inverse(),
+        }
+    }
+
+    fn pow(&self, exponent: u64) -> Dance {
+        Dance {
+            positions: self.positions.pow(exponent),
+            labels: self.labels.pow(exponent),
+        }
+    }
+
+    // exponent given as a decimal string of any length, a leading '-'
+    // dances backwards
+    fn pow_decimal(&self, exponent: &str) -> Dance {
+        let (dance, digits) = if exponent.starts_with('-') {
+            (self.inverse(), &exponent[1..])
+        } else {
+            (self.clone(), exponent)
+        };
+
+        let mut result = dance.pow(0);
+        for digit in digits.chars() {
+            let digit = digit.to_digit(10).expect("invalid count") as u64;
+            result = result.pow(10).then(&dance.pow(digit));
+        }
+        result
+    }
+
+    fn perform(&self, alphabet: &Vec<char>) -> Vec<char> {
+        let renames = self.labels.inverse();
+
+        self.positions.0
+            .iter()
+            .map(|label| alphabet[renames.0[*label]])
+            .collect()
     }
 }
 
-fn generate_initial_programs() -> Vec<char> {
-    (b'a'..b'q').map(|ch| ch as char).collect()
+fn generate_initial_programs(num_programs: usize) -> Vec<char> {
+    (b'a'..b'z' + 1).take(num_programs).map(|ch| ch as char).collect()
 }
 
 fn main() {
+    let mut alphabet = generate_initial_programs(16);
+    let mut count = String::from("1000000000");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--programs" => {
+                let num_programs = args.next().expect("missing count").parse::<usize>().expect("invalid count");
+                assert!(num_programs <= 26, "use --alphabet for more than 26 programs");
+                alphabet = generate_initial_programs(num_programs);
+            }
+            "--alphabet" => alphabet = args.next().expect("missing alphabet").chars().collect(),
+            _ => count = arg,
+        }
+    }
+
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).expect("io error");
-    let moves = parse_moves(&input);
-    let programs = apply_permutation_count(&moves, 1000000000);
+    let moves = match parse_moves(input.trim(), &alphabet) {
+        Ok(moves) => moves,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let dance = Dance::compile(&moves, alphabet.len()).pow_decimal(&count);
+    let programs = dance.perform(&alphabet);
     println!("{}", programs.iter().collect::<String>());
 }
@@ -24,9 +24,131 @@ fn get_zero_successor(num_steps: usize, step_size: usize) -> usize {
     zero_successor
 }
 
+// free slots of the final buffer as one bit each, with a binary indexed
+// tree counting them per 64 slot block to keep the tree cache friendly
+struct FreeSlots {
+    tree: Vec<u32>,
+    blocks: Vec<u64>,
+}
+
+impl FreeSlots {
+    fn new(num_slots: usize) -> FreeSlots {
+        let num_blocks = (num_slots + 63) / 64;
+        let mut blocks = vec![!0u64; num_blocks];
+        if num_slots % 64 != 0 {
+            blocks[num_blocks - 1] = (1 << (num_slots % 64)) - 1;
+        }
+
+        let mut tree = vec![0; num_blocks + 1];
+        for i in 1..num_blocks + 1 {
+            tree[i] += blocks[i - 1].count_ones();
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= num_blocks {
+                tree[parent] += tree[i];
+            }
+        }
+
+        FreeSlots {
+            tree: tree,
+            blocks: blocks,
+        }
+    }
+
+    // finds and takes the free slot preceded by exactly rank free slots,
+    // tree nodes skipped on the way down are the ones covering its block
+    fn take(&mut self, rank: usize) -> usize {
+        let mut remaining = rank as u32;
+        let mut block = 0;
+        let mut step = (self.tree.len() - 1).next_power_of_two();
+
+        while step > 0 {
+            let next = block + step;
+            if next < self.tree.len() {
+                if self.tree[next] <= remaining {
+                    block = next;
+                    remaining -= self.tree[next];
+                } else {
+                    self.tree[next] -= 1;
+                }
+            }
+            step >>= 1;
+        }
+
+        let mut bits = self.blocks[block];
+        for _ in 0..remaining {
+            bits &= bits - 1;
+        }
+        let bit = bits.trailing_zeros() as usize;
+        self.blocks[block] &= !(1 << bit);
+
+        block * 64 + bit
+    }
+}
+
+// full buffer after a number of steps, answering arbitrary queries
+//
+// insertion indices only depend on the current buffer length so they are
+// computed first; walking them backwards, every value lands in the free
+// slot matching its insertion index since only later values end up ahead
+struct Buffer {
+    items: Vec<u32>,
+}
+
+impl Buffer {
+    fn new(num_steps: usize, step_size: usize) -> Buffer {
+        let mut insert_indices: Vec<u32> = Vec::with_capacity(num_steps);
+        let mut position: usize = 0;
+
+        for step in 0..num_steps {
+            position = (position + step_size) % (step + 1) + 1;
+            insert_indices.push(position as u32);
+        }
+
+        let mut items = vec![0; num_steps + 1];
+        let mut free_slots = FreeSlots::new(num_steps + 1);
+
+        for value in (1..num_steps + 1).rev() {
+            let slot = free_slots.take(insert_indices[value - 1] as usize);
+            items[slot] = value as u32;
+        }
+        // value 0 takes the last free slot, which already holds 0
+
+        Buffer { items: items }
+    }
+
+    fn value_at(&self, index: usize) -> u32 {
+        self.items[index % self.items.len()]
+    }
+
+    fn index_of(&self, value: u32) -> Option<usize> {
+        self.items.iter().position(|item| *item == value)
+    }
+
+    fn successor_of(&self, value: u32) -> Option<u32> {
+        self.index_of(value).map(|index| self.value_at(index + 1))
+    }
+}
+
 fn main() {
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).expect("io error");
     let step_size = input.trim().parse::<usize>().expect("invalid step size");
-    println!("{}", get_zero_successor(50000000, step_size));
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let num_steps = args.get(2).map_or(50000000, |arg| arg.parse::<usize>().expect("invalid step count"));
+    let query_arg = || args.get(1).expect("missing query argument").parse::<u32>().expect("invalid query argument");
+
+    match args.get(0).map(|arg| arg.as_str()) {
+        None => println!("{}", get_zero_successor(num_steps, step_size)),
+        Some("after") => {
+            let buffer = Buffer::new(num_steps, step_size);
+            println!("{}", buffer.successor_of(query_arg()).expect("value not in buffer"));
+        }
+        Some("at") => println!("{}", Buffer::new(num_steps, step_size).value_at(query_arg() as usize)),
+        Some("index") => {
+            let buffer = Buffer::new(num_steps, step_size);
+            println!("{}", buffer.index_of(query_arg()).expect("value not in buffer"));
+        }
+        Some(mode) => panic!("unknown mode: {}", mode),
+    }
 }
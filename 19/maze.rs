@@ -0,0 +1,293 @@
+// tube maze walker shared by both parts, which only differ in what they
+// print by default
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::BufRead;
+use std::ops;
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+struct Coord(i32, i32);
+
+impl ops::Add for Coord {
+    type Output = Coord;
+
+    fn add(self, rhs: Coord) -> Coord {
+        Coord(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
+impl ops::Sub<Coord> for Coord {
+    type Output = Coord;
+
+    fn sub(self, rhs: Coord) -> Coord {
+        Coord(self.0 - rhs.0, self.1 - rhs.1)
+    }
+}
+
+#[derive(Debug)]
+enum MazeError {
+    Empty,
+    MissingEntry,
+    DeadEnd(Coord),
+    // junction with more than one way to turn, listing the candidates
+    AmbiguousJunction(Coord, Vec<Coord>),
+    Loop(Coord),
+    InvalidChar(char, Coord),
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MazeError::Empty => write!(f, "empty maze"),
+            MazeError::MissingEntry => write!(f, "no entry on the maze border"),
+            MazeError::DeadEnd(pos) => write!(f, "dead end at {:?}", pos),
+            MazeError::AmbiguousJunction(pos, ref candidates) => {
+                write!(f, "ambiguous junction at {:?}, could continue to {:?}", pos, candidates)
+            }
+            MazeError::Loop(pos) => write!(f, "path loops at {:?}", pos),
+            MazeError::InvalidChar(ch, pos) => write!(f, "invalid char {:?} at {:?}", ch, pos),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, MazeError>;
+
+// polyline runs from the start through every turn to the last visited
+// position
+struct Route {
+    letters: String,
+    num_steps: usize,
+    turns: Vec<Coord>,
+    polyline: Vec<Coord>,
+}
+
+struct Maze {
+    map: Vec<Vec<char>>,
+    width: usize,
+    height: usize,
+}
+
+impl Maze {
+    // rows shorter than the widest one are padded with spaces, since
+    // editors tend to strip trailing whitespace; blank rows above and below
+    // the maze are dropped so entries are looked for on its actual border
+    fn load(reader: &mut BufRead) -> Result<Maze> {
+        let mut map = reader.lines()
+            .map(|line| line.expect("io error").chars().collect::<Vec<char>>())
+            .collect::<Vec<_>>();
+
+        let is_blank = |row: &Vec<char>| row.iter().all(|ch| *ch == ' ');
+        while map.last().map_or(false, &is_blank) {
+            map.pop();
+        }
+        let num_leading = map.iter().take_while(|row| is_blank(row)).count();
+        map.drain(..num_leading);
+
+        let width = map.iter().map(|row| row.len()).max().unwrap_or(0);
+        if width == 0 {
+            return Err(MazeError::Empty);
+        }
+        for row in map.iter_mut() {
+            row.resize(width, ' ');
+        }
+
+        Ok(Maze {
+            height: map.len(),
+            width: width,
+            map: map,
+        })
+    }
+
+    fn walk(&self) -> Result<Route> {
+        let (start, direction) = *self.find_entries().first().ok_or(MazeError::MissingEntry)?;
+        self.walk_from(start, direction)
+    }
+
+    fn walk_from(&self, start: Coord, direction: Coord) -> Result<Route> {
+        let mut route = Route {
+            letters: String::new(),
+            num_steps: 1,
+            turns: Vec::new(),
+            polyline: vec![start],
+        };
+        if let ch @ 'A'...'Z' = self.get(&start) {
+            route.letters.push(ch);
+        }
+
+        let mut visited = HashSet::new();
+        let mut previous_pos = start;
+        let mut pos = start + direction;
+
+        loop {
+            if !visited.insert((pos, pos - previous_pos)) {
+                return Err(MazeError::Loop(pos));
+            }
+
+            match self.get(&pos) {
+                ch @ 'A'...'Z' => {
+                    route.letters.push(ch);
+                    self.advance_position(&mut pos, &mut previous_pos);
+                }
+                '|' | '-' => {
+                    self.advance_position(&mut pos, &mut previous_pos);
+                }
+                '+' => {
+                    let next_pos = self.get_changed_direction(pos, previous_pos)?;
+                    route.turns.push(pos);
+                    route.polyline.push(pos);
+                    previous_pos = pos;
+                    pos = next_pos;
+                }
+                ' ' => {
+                    break;
+                }
+                ch => {
+                    return Err(MazeError::InvalidChar(ch, pos));
+                }
+            }
+            route.num_steps += 1;
+        }
+
+        route.polyline.push(previous_pos);
+        Ok(route)
+    }
+
+    // tubes leading into the maze from any border along with the direction
+    // to enter them, checked from the top row down to the bottom one
+    fn find_entries(&self) -> Vec<(Coord, Coord)> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let mut entries = Vec::new();
+
+        {
+            let mut check = |pos: Coord, direction: Coord, tube: char| {
+                if self.get(&pos) == tube {
+                    entries.push((pos, direction));
+                }
+            };
+
+            for x in 0..width {
+                check(Coord(x, 0), Coord(0, 1), '|');
+            }
+            for y in 0..height {
+                check(Coord(0, y), Coord(1, 0), '-');
+                check(Coord(width - 1, y), Coord(-1, 0), '-');
+            }
+            for x in 0..width {
+                check(Coord(x, height - 1), Coord(0, -1), '|');
+            }
+        }
+
+        entries
+    }
+
+    // untraveled tubes are replaced with dots, or with color enabled the
+    // traveled ones are highlighted instead
+    fn render(&self, route: &Route, color: bool) -> String {
+        let mut traveled = vec![vec![false; self.width]; self.height];
+        for segment in route.polyline.windows(2) {
+            let (from, to) = (segment[0], segment[1]);
+            let delta = Coord((to.0 - from.0).signum(), (to.1 - from.1).signum());
+            let mut pos = from;
+            loop {
+                traveled[pos.1 as usize][pos.0 as usize] = true;
+                if pos == to {
+                    break;
+                }
+                pos = pos + delta;
+            }
+        }
+
+        let mut output = String::new();
+        for (y, row) in self.map.iter().enumerate() {
+            for (x, ch) in row.iter().enumerate() {
+                match (*ch, traveled[y][x], color) {
+                    (' ', _, _) => output.push(' '),
+                    (ch, true, true) => output.push_str(&format!("\x1b[1;31m{}\x1b[0m", ch)),
+                    (ch, true, false) | (ch, false, true) => output.push(ch),
+                    (_, false, false) => output.push('.'),
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    fn advance_position(&self, current_pos: &mut Coord, previous_pos: &mut Coord) {
+        let delta = *current_pos - *previous_pos;
+        *previous_pos = *current_pos;
+        *current_pos = *current_pos + delta
+    }
+
+    fn get_changed_direction(&self, current_pos: Coord, previous_pos: Coord) -> Result<Coord> {
+        let delta = current_pos - previous_pos;
+        let advanced_pos = current_pos + delta;
+
+        let offsets = [Coord(-1, 0), Coord(0, 1), Coord(1, 0), Coord(0, -1)];
+        let candidates = offsets.iter()
+            .map(|offset| current_pos + *offset)
+            .filter(|next_pos| self.get(next_pos) != ' ' && *next_pos != previous_pos && *next_pos != advanced_pos)
+            .collect::<Vec<_>>();
+
+        match candidates.len() {
+            0 => Err(MazeError::DeadEnd(current_pos)),
+            1 => Ok(candidates[0]),
+            _ => Err(MazeError::AmbiguousJunction(current_pos, candidates)),
+        }
+    }
+
+    fn get(&self, coord: &Coord) -> char {
+        let &Coord(x, y) = coord;
+        let x = x as usize;
+        let y = y as usize;
+
+        if y < self.map.len() {
+            if x < self.map[y].len() {
+                return self.map[y][x];
+            }
+        }
+
+        ' '
+    }
+}
+
+fn print_route(route: &Route) {
+    println!("letters: {}", route.letters);
+    println!("steps: {}", route.num_steps);
+    let vertices = route.polyline.iter().map(|pos| format!("{},{}", pos.0, pos.1)).collect::<Vec<_>>();
+    println!("turns: {}", route.turns.len());
+    println!("polyline: {}", vertices.join(" "));
+}
+
+// entry point of both parts, default_output is the mode used when none is
+// given on the command line
+pub fn main(default_output: &str) {
+    let stdin = std::io::stdin();
+    let result = Maze::load(&mut stdin.lock()).and_then(|maze| {
+        let route = maze.walk()?;
+        Ok((maze, route))
+    });
+    let (maze, route) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mode = std::env::args().nth(1).unwrap_or_else(|| default_output.to_string());
+
+    match mode.as_str() {
+        "letters" => println!("{}", route.letters),
+        "steps" => println!("{}", route.num_steps),
+        "route" => print_route(&route),
+        "entries" => {
+            for (pos, direction) in maze.find_entries() {
+                println!("{},{} heading {},{}", pos.0, pos.1, direction.0, direction.1);
+            }
+        }
+        "render" => print!("{}", maze.render(&route, false)),
+        "render-color" => print!("{}", maze.render(&route, true)),
+        mode => panic!("unknown mode: {}", mode),
+    }
+}
@@ -1,22 +1,11 @@
 extern crate regex;
 
-use std::cmp::Ordering;
 use std::io::BufRead;
 use regex::Regex;
 
 #[derive(Debug)]
 struct Vector(i32, i32, i32);
 
-impl Vector {
-    fn manhattan(&self) -> i32 {
-        self.0.abs() + self.1.abs() + self.2.abs()
-    }
-}
-
-fn manhattan_order(a: &Vector, b: &Vector) -> Ordering {
-    a.manhattan().cmp(&b.manhattan())
-}
-
 #[derive(Debug)]
 struct Particle {
     position: Vector,
@@ -52,18 +41,180 @@ fn load_particles(reader: &mut BufRead) -> Vec<Particle> {
     particles
 }
 
-fn get_slowest_particle(particles: &Vec<Particle>) -> usize {
-    fn ord(a: &Particle, b: &Particle) -> Ordering {
-        manhattan_order(&a.acceleration, &b.acceleration)
-            .then(manhattan_order(&a.velocity, &b.velocity))
-            .then(manhattan_order(&a.position, &b.position))
+// twice the value of a coordinate after t ticks, as a polynomial in t
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Quadratic {
+    a: i64,
+    b: i64,
+    c: i64,
+}
+
+impl Quadratic {
+    fn for_axis(p: i32, v: i32, a: i32) -> Quadratic {
+        let (p, v, a) = (p as i64, v as i64, a as i64);
+        Quadratic { a: a, b: 2 * v + a, c: 2 * p }
+    }
+
+    fn eval(&self, t: i64) -> i64 {
+        (self.a * t + self.b) * t + self.c
+    }
+
+    fn neg(&self) -> Quadratic {
+        Quadratic { a: -self.a, b: -self.b, c: -self.c }
+    }
+
+    fn add(&self, other: &Quadratic) -> Quadratic {
+        Quadratic { a: self.a + other.a, b: self.b + other.b, c: self.c + other.c }
+    }
+
+    fn sub(&self, other: &Quadratic) -> Quadratic {
+        self.add(&other.neg())
+    }
+
+    // sign the polynomial settles on for large t
+    fn sign_at_infinity(&self) -> i64 {
+        if self.a != 0 {
+            self.a.signum()
+        } else if self.b != 0 {
+            self.b.signum()
+        } else {
+            self.c.signum()
+        }
+    }
+
+    // first tick from which the polynomial keeps its sign at infinity
+    // (strictly, so a root counts as a sign change); 0 if it never changes
+    fn settle_tick(&self) -> i64 {
+        let f = if self.sign_at_infinity() < 0 { self.neg() } else { *self };
+        if f.a == 0 && f.b == 0 {
+            return 0;
+        }
+
+        // the polynomial decreases over the ticks before rising_from and
+        // increases from there on, so its last non-positive tick is either
+        // the one just before rising_from or found by searching past it
+        let rising_from = if f.a == 0 || f.b >= 0 {
+            0
+        } else {
+            (-f.b + 2 * f.a - 1) / (2 * f.a)
+        };
+
+        if f.eval(rising_from) > 0 {
+            return if rising_from > 0 && f.eval(rising_from - 1) <= 0 { rising_from } else { 0 };
+        }
+
+        let mut low = rising_from;
+        let mut high = rising_from + 1;
+        while f.eval(high) <= 0 {
+            low = high;
+            high += high - rising_from;
+        }
+
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            if f.eval(middle) > 0 {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+
+        high
+    }
+}
+
+// twice the manhattan distance from the origin, per axis
+struct Trajectory {
+    axes: [Quadratic; 3],
+    // distance polynomial valid once every axis has settled its sign
+    distance: Quadratic,
+    settled_from: i64,
+}
+
+impl Trajectory {
+    fn new(particle: &Particle) -> Trajectory {
+        let axes = [
+            Quadratic::for_axis(particle.position.0, particle.velocity.0, particle.acceleration.0),
+            Quadratic::for_axis(particle.position.1, particle.velocity.1, particle.acceleration.1),
+            Quadratic::for_axis(particle.position.2, particle.velocity.2, particle.acceleration.2),
+        ];
+
+        let mut distance = Quadratic { a: 0, b: 0, c: 0 };
+        let mut settled_from = 0;
+
+        for axis in axes.iter() {
+            distance = if axis.sign_at_infinity() < 0 {
+                distance.sub(axis)
+            } else {
+                distance.add(axis)
+            };
+            settled_from = std::cmp::max(settled_from, axis.settle_tick());
+        }
+
+        Trajectory {
+            axes: axes,
+            distance: distance,
+            settled_from: settled_from,
+        }
+    }
 
+    fn distance_at(&self, t: i64) -> i64 {
+        self.axes.iter().map(|axis| axis.eval(t).abs()).sum()
     }
+}
 
-    particles.iter()
-        .enumerate()
-        .min_by(|&(_, particle_a), &(_, particle_b)| ord(particle_a, particle_b))
-        .map(|(index, _)| index).expect("no particle found")
+#[derive(Debug)]
+struct Nearest {
+    // more than one entry if particles stay equally distant forever
+    particles: Vec<usize>,
+    // first tick from which every other particle is strictly further away
+    stable_from: i64,
+}
+
+fn get_nearest_particles(particles: &[Particle]) -> Nearest {
+    let trajectories: Vec<Trajectory> = particles.iter().map(Trajectory::new).collect();
+
+    let best = trajectories.iter()
+        .map(|trajectory| trajectory.distance)
+        .min()
+        .expect("no particle found");
+
+    let nearest: Vec<usize> = (0..trajectories.len())
+        .filter(|&index| trajectories[index].distance == best)
+        .collect();
+
+    let mut stable_from = 0;
+
+    for &winner in &nearest {
+        let winner = &trajectories[winner];
+
+        for (index, other) in trajectories.iter().enumerate() {
+            if nearest.contains(&index) {
+                continue;
+            }
+
+            let settled_from = std::cmp::max(winner.settled_from, other.settled_from);
+            let gap = other.distance.sub(&winner.distance);
+            let gap_settled_from = gap.settle_tick();
+
+            // once both distances are polynomials the gap only closes
+            // before its own settle tick; earlier ticks are checked directly
+            let stable = if gap_settled_from > settled_from {
+                gap_settled_from
+            } else {
+                (0..settled_from).rev()
+                    .find(|&t| other.distance_at(t) <= winner.distance_at(t))
+                    .map_or(0, |t| t + 1)
+            };
+
+            stable_from = std::cmp::max(stable_from, stable);
+        }
+    }
+
+    Nearest {
+        particles: nearest,
+        stable_from: stable_from,
+    }
 }
 
 fn set_particle_field(particle: &mut Particle,
@@ -96,5 +247,20 @@ fn main() {
 
     let particles = load_particles(&mut stdin.lock());
 
-    println!("{}", get_slowest_particle(&particles));
+    let nearest = get_nearest_particles(&particles);
+
+    let indices: Vec<String> = nearest.particles.iter().map(|index| index.to_string()).collect();
+
+    match std::env::args().nth(1).as_ref().map(|arg| arg.as_str()) {
+        None => {
+            println!("{}", indices.join(" "));
+        }
+        Some("stable") => {
+            println!("nearest: {}", indices.join(" "));
+            println!("stable from tick: {}", nearest.stable_from);
+        }
+        Some(mode) => {
+            panic!("unknown mode: {}", mode);
+        }
+    }
 }
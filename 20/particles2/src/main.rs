@@ -1,7 +1,6 @@
 extern crate regex;
 
-use std::collections::HashMap;
-use std::io::BufRead;
+use std::io::{BufRead, BufWriter, Write};
 use std::ops::Add;
 use regex::Regex;
 
@@ -32,12 +31,206 @@ impl Particle {
         }
     }
 
-    fn update(&mut self) {
-        self.velocity = self.velocity + self.acceleration;
-        self.position = self.position + self.velocity;
+    fn update(&mut self, integration: Integration) {
+        match integration {
+            Integration::VelocityFirst => {
+                self.velocity = self.velocity + self.acceleration;
+                self.position = self.position + self.velocity;
+            }
+            Integration::PositionFirst => {
+                self.position = self.position + self.velocity;
+                self.velocity = self.velocity + self.acceleration;
+            }
+        }
+    }
+}
+
+// order in which a tick applies acceleration and velocity
+#[derive(Clone, Copy, Debug)]
+enum Integration {
+    // the puzzle's rule, the particle moves with the updated velocity
+    VelocityFirst,
+    // textbook explicit euler, the particle moves with the old velocity
+    PositionFirst,
+}
+
+impl Integration {
+    fn parse(name: &str) -> Integration {
+        match name {
+            "velocity-first" => Integration::VelocityFirst,
+            "position-first" => Integration::PositionFirst,
+            _ => panic!("unknown integration: {}", name),
+        }
+    }
+}
+
+// open addressing table of occupied positions that is reused between ticks,
+// slots are only valid when their stamp matches so resetting costs nothing
+struct SpatialHash {
+    slots: Vec<(u32, usize)>,
+    stamp: u32,
+}
+
+impl SpatialHash {
+    fn new() -> SpatialHash {
+        SpatialHash {
+            slots: Vec::new(),
+            stamp: 0,
+        }
+    }
+
+    fn reset(&mut self, num_particles: usize) {
+        let size = std::cmp::max(16, (2 * num_particles).next_power_of_two());
+        if size > self.slots.len() {
+            self.slots = vec![(0, 0); size];
+            self.stamp = 0;
+        }
+
+        self.stamp = self.stamp.wrapping_add(1);
+        if self.stamp == 0 {
+            for slot in self.slots.iter_mut() {
+                *slot = (0, 0);
+            }
+            self.stamp = 1;
+        }
+    }
+
+    fn hash(position: &Vector) -> usize {
+        let h = (position.0 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (position.1 as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+            ^ (position.2 as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+        (h ^ (h >> 32)) as usize
+    }
+
+    // records the particle at its position, returning the particle that
+    // got there first this tick if there is one
+    fn insert(&mut self, particles: &[Particle], index: usize) -> Option<usize> {
+        let mask = self.slots.len() - 1;
+        let position = particles[index].position;
+        let mut slot = SpatialHash::hash(&position) & mask;
+
+        loop {
+            let (stamp, other) = self.slots[slot];
+            if stamp != self.stamp {
+                self.slots[slot] = (self.stamp, index);
+                return None;
+            }
+            if particles[other].position == position {
+                return Some(other);
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+}
+
+struct Simulation {
+    particles: Vec<Particle>,
+    // input line of each remaining particle
+    ids: Vec<usize>,
+    integration: Integration,
+    tick: u64,
+    hash: SpatialHash,
+    collided: Vec<bool>,
+}
+
+impl Simulation {
+    fn new(particles: Vec<Particle>, integration: Integration) -> Simulation {
+        let ids = (0..particles.len()).collect();
+
+        Simulation {
+            particles: particles,
+            ids: ids,
+            integration: integration,
+            tick: 0,
+            hash: SpatialHash::new(),
+            collided: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    // removes every particle sharing its position with another one,
+    // returns how many were removed
+    fn remove_collisions(&mut self) -> usize {
+        let num_particles = self.particles.len();
+
+        self.hash.reset(num_particles);
+        self.collided.clear();
+        self.collided.resize(num_particles, false);
+
+        for index in 0..num_particles {
+            if let Some(other) = self.hash.insert(&self.particles, index) {
+                self.collided[index] = true;
+                self.collided[other] = true;
+            }
+        }
+
+        let mut kept = 0;
+        for index in 0..num_particles {
+            if !self.collided[index] {
+                self.particles[kept] = self.particles[index];
+                self.ids[kept] = self.ids[index];
+                kept += 1;
+            }
+        }
+        self.particles.truncate(kept);
+        self.ids.truncate(kept);
+
+        num_particles - kept
+    }
+
+    // resolves collisions at the current tick and moves on to the next one
+    fn step(&mut self) -> usize {
+        let removed = self.remove_collisions();
+
+        for particle in self.particles.iter_mut() {
+            particle.update(self.integration);
+        }
+        self.tick += 1;
+
+        removed
+    }
+
+    // runs until no collision happened for the given number of ticks
+    fn run_until_idle(&mut self, idle_ticks: u64) -> usize {
+        let mut idle_iters = 0;
+
+        while self.len() > 0 && idle_iters < idle_ticks {
+            if self.step() > 0 {
+                idle_iters = 0;
+            }
+            idle_iters += 1;
+        }
+
+        self.len()
+    }
+
+    fn write_csv_rows<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for (id, particle) in self.ids.iter().zip(self.particles.iter()) {
+            let position = &particle.position;
+            writeln!(writer, "{},{},{},{},{}", self.tick, id, position.0, position.1, position.2)?;
+        }
+        Ok(())
     }
 }
 
+// writes the position of every particle alive at each tick, including the
+// tick it collides at
+fn export_trajectories<W: Write>(simulation: &mut Simulation,
+                                 num_ticks: u64,
+                                 writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "tick,particle,x,y,z")?;
+
+    for _ in 0..num_ticks + 1 {
+        simulation.write_csv_rows(writer)?;
+        simulation.step();
+    }
+
+    writer.flush()
+}
+
 fn load_particles(reader: &mut BufRead) -> Vec<Particle> {
     let mut particles = Vec::new();
     let re = Regex::new(r"([pva])=<(-?[0-9]+),(-?[0-9]+),(-?[0-9]+)>").expect("bad regex");
@@ -56,46 +249,110 @@ fn load_particles(reader: &mut BufRead) -> Vec<Particle> {
     particles
 }
 
-fn get_remaining_particles(particles: &mut Vec<Particle>) -> usize {
-    let mut idle_iters = 0;
-    let mut prev_len = particles.len();
+// times at which one axis of two particles coincides
+enum AxisMatch {
+    Always,
+    At(Vec<i64>),
+}
 
-    loop {
-        remove_colliding_particles(particles);
+// position after t ticks is p + v*t + a*t*(t+1)/2 since velocity is updated
+// first, so doubled differences of two particles give the quadratic
+// da*t^2 + (2*dv + da)*t + 2*dp = 0 which is solved for integer t >= 0
+fn solve_axis(dp: i64, dv: i64, da: i64) -> AxisMatch {
+    let (a, b, c) = (da, 2 * dv + da, 2 * dp);
 
-        let new_len = particles.len();
-        if new_len < prev_len {
-            prev_len = new_len;
-            idle_iters = 0;
+    if a == 0 {
+        if b == 0 {
+            return if c == 0 { AxisMatch::Always } else { AxisMatch::At(Vec::new()) };
         }
+        return AxisMatch::At(if c % b == 0 && -c / b >= 0 { vec![-c / b] } else { Vec::new() });
+    }
 
-        for particle in particles.iter_mut() {
-            particle.update();
-        }
+    let discriminant = b * b - 4 * a * c;
+    if discriminant < 0 {
+        return AxisMatch::At(Vec::new());
+    }
+    let root = integer_sqrt(discriminant);
+    if root * root != discriminant {
+        return AxisMatch::At(Vec::new());
+    }
 
-        if prev_len == 0 {
-            break;
-        }
+    let mut times = [-b - root, -b + root].iter()
+        .filter(|numerator| *numerator % (2 * a) == 0)
+        .map(|numerator| numerator / (2 * a))
+        .filter(|t| *t >= 0)
+        .collect::<Vec<_>>();
+    times.sort();
+    times.dedup();
+    AxisMatch::At(times)
+}
 
-        idle_iters += 1;
-        if idle_iters == 1000 {
-            break;
-        }
+fn integer_sqrt(n: i64) -> i64 {
+    let mut root = (n as f64).sqrt() as i64;
+    while root * root > n {
+        root -= 1;
     }
+    while (root + 1) * (root + 1) <= n {
+        root += 1;
+    }
+    root
+}
 
-    particles.len()
+fn axis_position(p: i64, v: i64, a: i64, t: i64) -> i64 {
+    p + v * t + a * t * (t + 1) / 2
 }
 
-fn remove_colliding_particles(particles: &mut Vec<Particle>) {
-    let mut map: HashMap<Vector, usize> = HashMap::new();
+fn first_collision(first: &Particle, second: &Particle) -> Option<i64> {
+    let axes = [
+        (first.position.0, first.velocity.0, first.acceleration.0,
+         second.position.0, second.velocity.0, second.acceleration.0),
+        (first.position.1, first.velocity.1, first.acceleration.1,
+         second.position.1, second.velocity.1, second.acceleration.1),
+        (first.position.2, first.velocity.2, first.acceleration.2,
+         second.position.2, second.velocity.2, second.acceleration.2),
+    ];
 
-    for particle in particles.iter() {
-        *map.entry(particle.position).or_insert(0) += 1;
+    // candidates come from any axis with finitely many solutions, the other
+    // axes only need checking at those times
+    for &(p1, v1, a1, p2, v2, a2) in axes.iter() {
+        if let AxisMatch::At(times) = solve_axis(p1 - p2, v1 - v2, a1 - a2) {
+            return times.into_iter().find(|t| {
+                axes.iter().all(|&(p1, v1, a1, p2, v2, a2)| {
+                    axis_position(p1, v1, a1, *t) == axis_position(p2, v2, a2, *t)
+                })
+            });
+        }
     }
 
-    particles.retain(|particle| *map.get(&particle.position).unwrap() <= 1);
+    // identical particles
+    Some(0)
 }
 
+// returns the tick at which each particle gets destroyed, processing
+// collisions in time order so that destroyed particles can't collide later
+fn get_destruction_times(particles: &[Particle]) -> Vec<Option<i64>> {
+    let mut collisions = Vec::new();
+    for i in 0..particles.len() {
+        for j in i + 1..particles.len() {
+            if let Some(t) = first_collision(&particles[i], &particles[j]) {
+                collisions.push((t, i, j));
+            }
+        }
+    }
+    collisions.sort();
+
+    let mut destroyed_at: Vec<Option<i64>> = vec![None; particles.len()];
+    for &(t, i, j) in &collisions {
+        let alive_i = destroyed_at[i].map_or(true, |destroyed| destroyed == t);
+        let alive_j = destroyed_at[j].map_or(true, |destroyed| destroyed == t);
+        if alive_i && alive_j {
+            destroyed_at[i] = Some(t);
+            destroyed_at[j] = Some(t);
+        }
+    }
+
+    destroyed_at
+}
 
 fn set_particle_field(particle: &mut Particle,
                       field_name: &str,
@@ -122,10 +379,69 @@ fn set_particle_field(particle: &mut Particle,
     }
 }
 
+struct Options {
+    mode: Option<String>,
+    integration: Integration,
+    num_ticks: u64,
+    idle_ticks: u64,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Options {
+        let mut options = Options {
+            mode: None,
+            integration: Integration::VelocityFirst,
+            num_ticks: 100,
+            idle_ticks: 1000,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--integration" => {
+                    options.integration = Integration::parse(&args.next().expect("missing integration"));
+                }
+                "--ticks" => {
+                    options.num_ticks = args.next().expect("missing count").parse().expect("invalid count");
+                }
+                "--idle" => {
+                    options.idle_ticks = args.next().expect("missing count").parse().expect("invalid count");
+                }
+                _ if options.mode.is_none() => options.mode = Some(arg),
+                _ => panic!("unknown option: {}", arg),
+            }
+        }
+        options
+    }
+}
+
 fn main() {
+    let options = Options::parse(std::env::args().skip(1));
     let stdin = std::io::stdin();
 
-    let mut particles = load_particles(&mut stdin.lock());
+    let particles = load_particles(&mut stdin.lock());
 
-    println!("{}", get_remaining_particles(&mut particles));
+    match options.mode.as_ref().map(|mode| mode.as_str()) {
+        None => {
+            let destroyed_at = get_destruction_times(&particles);
+            println!("{}", destroyed_at.iter().filter(|t| t.is_none()).count());
+        }
+        Some("deaths") => {
+            for (index, t) in get_destruction_times(&particles).iter().enumerate() {
+                if let Some(t) = *t {
+                    println!("{}: {}", index, t);
+                }
+            }
+        }
+        Some("simulate") => {
+            let mut simulation = Simulation::new(particles, options.integration);
+            println!("{}", simulation.run_until_idle(options.idle_ticks));
+        }
+        Some("csv") => {
+            let mut simulation = Simulation::new(particles, options.integration);
+            let stdout = std::io::stdout();
+            let mut writer = BufWriter::new(stdout.lock());
+            export_trajectories(&mut simulation, options.num_ticks, &mut writer).expect("io error");
+        }
+        Some(mode) => panic!("unknown mode: {}", mode),
+    }
 }
@@ -1,10 +1,11 @@
+extern crate gif;
+extern crate png;
 extern crate regex;
 
 use regex::Regex;
-use std::collections::HashMap;
 use std::io::{Read, Write};
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 struct Bitmap {
     data: Vec<bool>,
     dimension: usize,
@@ -18,121 +19,253 @@ impl Bitmap {
         }
     }
 
-    fn flip_x(&self) -> Bitmap {
-        let mut flipped = Bitmap::new(self.dimension);
-        for y in 0..self.dimension {
-            for x in 0..self.dimension {
-                flipped.set(x, y, self.get(self.dimension - x - 1, y));
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.data[y * self.dimension + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: bool) {
+        self.data[y * self.dimension + x] = value;
+    }
+
+    // encodes a 2x2 or 3x3 block as a bit per pixel, row by row
+    fn encode_block(&self, block_x: usize, block_y: usize, block_dim: usize) -> usize {
+        let mut code = 0;
+        for y in 0..block_dim {
+            for x in 0..block_dim {
+                if self.get(block_x + x, block_y + y) {
+                    code |= 1 << (y * block_dim + x);
+                }
             }
         }
-        flipped
+        code
     }
 
-    fn flip_y(&self) -> Bitmap {
-        let mut flipped = Bitmap::new(self.dimension);
-        for y in 0..self.dimension {
-            for x in 0..self.dimension {
-                flipped.set(x, y, self.get(x, self.dimension - y - 1));
+    fn encode(&self) -> usize {
+        self.encode_block(0, 0, self.dimension)
+    }
+
+    fn decode(code: usize, dimension: usize) -> Bitmap {
+        let mut bitmap = Bitmap::new(dimension);
+        for y in 0..dimension {
+            for x in 0..dimension {
+                bitmap.set(x, y, code & (1 << (y * dimension + x)) != 0);
+            }
+        }
+        bitmap
+    }
+
+    fn extend(&self, rule_table: &RuleTable) -> Bitmap {
+        let block_dim = if self.dimension % 2 == 0 { 2 } else { 3 };
+        let num_blocks = self.dimension / block_dim;
+        let mut extended = Bitmap::new(num_blocks * (block_dim + 1));
+
+        for by in 0..num_blocks {
+            for bx in 0..num_blocks {
+                let code = self.encode_block(bx * block_dim, by * block_dim, block_dim);
+                let output = rule_table.lookup(code, block_dim);
+
+                for y in 0..output.dimension {
+                    for x in 0..output.dimension {
+                        extended.set(bx * output.dimension + x, by * output.dimension + y, output.get(x, y));
+                    }
+                }
             }
         }
-        flipped
+
+        extended
+    }
+
+    fn count_pixels(&self) -> usize {
+        self.data.iter().filter(|p| **p).count()
     }
 
-    fn rotate(&self) -> Bitmap {
-        let mut rotated = Bitmap::new(self.dimension);
+    fn print(&self) {
+        let mut stdout = std::io::stdout();
         for y in 0..self.dimension {
             for x in 0..self.dimension {
-                rotated.set(self.dimension - y - 1, x, self.get(x, y));
+                stdout.write(if self.get(x, y) { b"#" } else { b"." }).expect("io error");
             }
+            stdout.write(b"\n").expect("io error");
         }
-        rotated
     }
 
-    fn get(&self, x: usize, y: usize) -> bool {
-        self.data[y * self.dimension + x]
+    // every pixel becomes a scale x scale square, set pixels are black
+    fn to_pbm(&self, scale: usize) -> Vec<u8> {
+        let size = self.dimension * scale;
+        let mut output = format!("P4\n{} {}\n", size, size).into_bytes();
+        for y in 0..size {
+            output.extend(self.pack_row(y / scale, scale, true));
+        }
+        output
     }
 
-    fn set(&mut self, x: usize, y: usize, value: bool) {
-        self.data[y * self.dimension + x] = value;
+    fn write_png<W: Write>(&self, writer: W, scale: usize) -> std::io::Result<()> {
+        use png::HasParameters;
+
+        let size = self.dimension * scale;
+        let mut data = Vec::with_capacity(size * (size + 7) / 8);
+        for y in 0..size {
+            data.extend(self.pack_row(y / scale, scale, false));
+        }
+
+        let mut encoder = png::Encoder::new(writer, size as u32, size as u32);
+        encoder.set(png::ColorType::Grayscale).set(png::BitDepth::One);
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(())
     }
 
-    fn extend(&self, rule_set: &HashMap<Bitmap, Bitmap>) -> Bitmap {
-        if self.dimension % 2 == 0 {
-            let slices = self.slice_vec(2);
-            Bitmap::join(&slices.iter().map(|s| s.apply_rule(rule_set)).collect::<Vec<_>>())
-        } else {
-            let slices = self.slice_vec(3);
-            Bitmap::join(&slices.iter().map(|s| s.apply_rule(rule_set)).collect::<Vec<_>>())
+    // one bit per scaled pixel, most significant first
+    fn pack_row(&self, y: usize, scale: usize, set_bit: bool) -> Vec<u8> {
+        let size = self.dimension * scale;
+        let mut row = vec![if set_bit { 0 } else { 0xff }; (size + 7) / 8];
+        for x in 0..size {
+            if self.get(x / scale, y) {
+                row[x / 8] ^= 0x80 >> (x % 8);
+            }
         }
+        row
     }
+}
+
+// all iterations as frames of an animated gif, each one stretched over the
+// canvas of the last and shown for delay hundredths of a second
+fn write_gif<W: Write>(frames: &[Bitmap], writer: W, scale: usize, delay: u16) -> std::io::Result<()> {
+    use gif::SetParameter;
 
-    fn slice_vec(&self, slice_dim: usize) -> Vec<Bitmap> {
-        let mut slices = Vec::new();
+    let size = frames.last().expect("no frames").dimension * scale;
+    assert!(size <= u16::max_value() as usize, "image too large for gif");
 
-        assert!(self.dimension % slice_dim == 0);
+    let mut encoder = gif::Encoder::new(writer, size as u16, size as u16, &[0xff, 0xff, 0xff, 0, 0, 0])?;
+    encoder.set(gif::Repeat::Infinite)?;
 
-        let num_slices = self.dimension / slice_dim;
-        for sy in 0..num_slices {
-            for sx in 0..num_slices {
-                slices.push(self.slice(sx * slice_dim, sy * slice_dim, slice_dim));
+    for bitmap in frames {
+        let mut pixels = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                let on = bitmap.get(x * bitmap.dimension / size, y * bitmap.dimension / size);
+                pixels.push(if on { 1 } else { 0 });
             }
         }
 
-        slices
+        let mut frame = gif::Frame::default();
+        frame.width = size as u16;
+        frame.height = size as u16;
+        frame.delay = delay;
+        frame.buffer = std::borrow::Cow::Owned(pixels);
+        encoder.write_frame(&frame)?;
     }
 
-    fn slice(&self, slice_x: usize, slice_y: usize, slice_dim: usize) -> Bitmap {
-        let mut slice = Bitmap::new(slice_dim);
+    Ok(())
+}
 
-        for y in 0..slice_dim {
-            for x in 0..slice_dim {
-                slice.set(x, y, self.get(slice_x + x, slice_y + y));
+impl std::fmt::Display for Bitmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for y in 0..self.dimension {
+            if y > 0 {
+                write!(f, "/")?;
+            }
+            for x in 0..self.dimension {
+                write!(f, "{}", if self.get(x, y) { '#' } else { '.' })?;
             }
         }
+        Ok(())
+    }
+}
 
-        slice
+// maps (x, y) through one of the 8 symmetries of a square: an optional
+// horizontal flip followed by 0-3 quarter turns
+fn apply_symmetry(x: usize, y: usize, dimension: usize, symmetry: usize) -> (usize, usize) {
+    let (mut x, mut y) = if symmetry & 4 != 0 { (dimension - x - 1, y) } else { (x, y) };
+    for _ in 0..symmetry & 3 {
+        let rotated = (dimension - y - 1, x);
+        x = rotated.0;
+        y = rotated.1;
     }
+    (x, y)
+}
 
-    fn apply_rule(&self, rule_set: &HashMap<Bitmap, Bitmap>) -> Bitmap {
-        rule_set.get(self).expect("unmatched rule").clone()
+fn transform_code(code: usize, dimension: usize, symmetry: usize) -> usize {
+    let mut transformed = 0;
+    for y in 0..dimension {
+        for x in 0..dimension {
+            if code & (1 << (y * dimension + x)) != 0 {
+                let (tx, ty) = apply_symmetry(x, y, dimension, symmetry);
+                transformed |= 1 << (ty * dimension + tx);
+            }
+        }
     }
+    transformed
+}
 
-    fn join(slices: &Vec<Bitmap>) -> Bitmap {
-        assert!(slices.len() > 0);
+// smallest encoding among all rotations and flips of the pattern
+fn canonical_code(code: usize, dimension: usize) -> usize {
+    (0..8).map(|symmetry| transform_code(code, dimension, symmetry)).min().expect("no symmetry")
+}
 
-        let slice_dim = slices[0].dimension;
-        let slice_cnt = (slices.len() as f64).sqrt() as usize;
-        assert!(slice_cnt * slice_cnt == slices.len());
+#[derive(Debug)]
+enum RuleWarning {
+    // same pattern (up to symmetry) listed again with the same output
+    Duplicate(Bitmap),
+    // same pattern (up to symmetry) listed again with another output, the
+    // later rule wins
+    Conflict(Bitmap, Bitmap, Bitmap),
+}
 
-        let mut joined = Bitmap::new(slice_dim * slice_cnt);
+impl std::fmt::Display for RuleWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            RuleWarning::Duplicate(ref pattern) => write!(f, "duplicate rule for {}", pattern),
+            RuleWarning::Conflict(ref pattern, ref previous, ref output) => {
+                write!(f, "conflicting rules for {}: {} replaced by {}", pattern, previous, output)
+            }
+        }
+    }
+}
 
-        for sy in 0..slice_cnt {
-            for sx in 0..slice_cnt {
-                let slice = &slices[sx + sy * slice_cnt];
-                for y in 0..slice_dim {
-                    for x in 0..slice_dim {
-                        let pixel = slice.get(x, y);
-                        joined.set(sx * slice_dim + x, sy * slice_dim + y, pixel);
-                    }
+// outputs of every 2x2 and 3x3 pattern, indexed by encoding
+struct RuleTable {
+    outputs2: Vec<Option<Bitmap>>,
+    outputs3: Vec<Option<Bitmap>>,
+}
+
+impl RuleTable {
+    fn new(rules: &[Rule]) -> (RuleTable, Vec<RuleWarning>) {
+        let mut warnings = Vec::new();
+        let mut canonical2: Vec<Option<&Bitmap>> = vec![None; 1 << 4];
+        let mut canonical3: Vec<Option<&Bitmap>> = vec![None; 1 << 9];
+
+        for &Rule(ref pattern, ref output) in rules {
+            let canonical = if pattern.dimension == 2 { &mut canonical2 } else { &mut canonical3 };
+            let slot = &mut canonical[canonical_code(pattern.encode(), pattern.dimension)];
+
+            match *slot {
+                Some(previous) if previous == output => {
+                    warnings.push(RuleWarning::Duplicate(pattern.clone()));
                 }
+                Some(previous) => {
+                    warnings.push(RuleWarning::Conflict(pattern.clone(), previous.clone(), output.clone()));
+                }
+                None => {}
             }
+            *slot = Some(output);
         }
 
-        joined
-    }
+        fn expand(canonical: &[Option<&Bitmap>], dimension: usize) -> Vec<Option<Bitmap>> {
+            (0..canonical.len())
+                .map(|code| canonical[canonical_code(code, dimension)].cloned())
+                .collect()
+        }
 
-    fn count_pixels(&self) -> usize {
-        self.data.iter().filter(|p| **p).count()
+        let table = RuleTable {
+            outputs2: expand(&canonical2, 2),
+            outputs3: expand(&canonical3, 3),
+        };
+        (table, warnings)
     }
 
-    fn print(&self) {
-        let mut stdout = std::io::stdout();
-        for y in 0..self.dimension {
-            for x in 0..self.dimension {
-                stdout.write(if self.get(x, y) { b"#" } else { b"." }).expect("io error");
-            }
-            stdout.write(b"\n").expect("io error");
-        }
+    fn lookup(&self, code: usize, dimension: usize) -> &Bitmap {
+        let outputs = if dimension == 2 { &self.outputs2 } else { &self.outputs3 };
+        outputs[code].as_ref().expect("unmatched rule")
     }
 }
 
@@ -181,33 +314,187 @@ fn initial_map() -> Bitmap {
     }
 }
 
-fn add_to_rule_set(rule_set: &mut HashMap<Bitmap, Bitmap>, rules: Vec<Rule>) {
-    for &Rule(ref src_bitmap, ref dst_bitmap) in rules.iter() {
-        rule_set.insert(src_bitmap.clone(), dst_bitmap.clone());
-        rule_set.insert(src_bitmap.flip_x(), dst_bitmap.clone());
-        rule_set.insert(src_bitmap.flip_y(), dst_bitmap.clone());
-        rule_set.insert(src_bitmap.flip_x().flip_y(), dst_bitmap.clone());
+// arbitrary size natural number in base 10^9 limbs, least significant first
+#[derive(Clone, Debug)]
+struct Natural(Vec<u32>);
+
+const LIMB_BASE: u64 = 1_000_000_000;
+
+impl Natural {
+    fn zero() -> Natural {
+        Natural(Vec::new())
+    }
 
-        let mut rotated = src_bitmap.clone();
-        for _ in 0..3 {
-            rotated = rotated.rotate();
-            rule_set.insert(rotated.clone(), dst_bitmap.clone());
+    fn from_u64(mut value: u64) -> Natural {
+        let mut limbs = Vec::new();
+        while value > 0 {
+            limbs.push((value % LIMB_BASE) as u32);
+            value /= LIMB_BASE;
         }
+        Natural(limbs)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // self += other * factor
+    fn add_multiple(&mut self, other: &Natural, factor: u32) {
+        let mut carry = 0u64;
+        let mut i = 0;
 
-        let mut rotated = src_bitmap.flip_x();
-        for _ in 0..3 {
-            rotated = rotated.rotate();
-            rule_set.insert(rotated.clone(), dst_bitmap.clone());
+        while i < other.0.len() || carry > 0 {
+            if i == self.0.len() {
+                self.0.push(0);
+            }
+            let product = other.0.get(i).map_or(0, |&limb| limb as u64 * factor as u64);
+            let sum = self.0[i] as u64 + product + carry;
+            self.0[i] = (sum % LIMB_BASE) as u32;
+            carry = sum / LIMB_BASE;
+            i += 1;
         }
+    }
+}
 
-        let mut rotated = src_bitmap.flip_y();
-        for _ in 0..3 {
-            rotated = rotated.rotate();
-            rule_set.insert(rotated.clone(), dst_bitmap.clone());
+impl std::fmt::Display for Natural {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0.split_last() {
+            None => write!(f, "0"),
+            Some((most_significant, rest)) => {
+                write!(f, "{}", most_significant)?;
+                for limb in rest.iter().rev() {
+                    write!(f, "{:09}", limb)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+// every 3 iterations a 3x3 block turns into a 9x9 area made of nine 3x3
+// blocks which never interact again, so instead of the image only the
+// number of blocks of each kind needs tracking
+struct BlockEvolution<'a> {
+    rule_table: &'a RuleTable,
+    // blocks (with multiplicity) a 3x3 block turns into after 3 iterations
+    children: Vec<Option<Vec<(usize, u32)>>>,
+}
+
+impl<'a> BlockEvolution<'a> {
+    fn new(rule_table: &'a RuleTable) -> BlockEvolution<'a> {
+        BlockEvolution {
+            rule_table: rule_table,
+            children: vec![None; 1 << 9],
+        }
+    }
+
+    fn expand(&self, code: usize, iterations: usize) -> Bitmap {
+        let mut bitmap = Bitmap::decode(code, 3);
+        for _ in 0..iterations {
+            bitmap = bitmap.extend(self.rule_table);
+        }
+        bitmap
+    }
+
+    fn compute_children(&mut self, code: usize) {
+        if self.children[code].is_some() {
+            return;
+        }
+
+        let expanded = self.expand(code, 3);
+        let mut children: Vec<(usize, u32)> = Vec::new();
+
+        for by in 0..3 {
+            for bx in 0..3 {
+                let child = expanded.encode_block(bx * 3, by * 3, 3);
+                match children.iter().position(|&(other, _)| other == child) {
+                    Some(index) => children[index].1 += 1,
+                    None => children.push((child, 1)),
+                }
+            }
+        }
+
+        self.children[code] = Some(children);
+    }
+
+    fn count_pixels(&mut self, initial: &Bitmap, iterations: usize) -> Natural {
+        assert!(initial.dimension == 3);
+
+        let mut counts = vec![Natural::zero(); 1 << 9];
+        counts[initial.encode()] = Natural::from_u64(1);
+
+        for _ in 0..iterations / 3 {
+            let mut next = vec![Natural::zero(); 1 << 9];
+
+            for code in 0..counts.len() {
+                if counts[code].is_zero() {
+                    continue;
+                }
+                self.compute_children(code);
+                for &(child, multiplicity) in self.children[code].as_ref().expect("no children") {
+                    next[child].add_multiple(&counts[code], multiplicity);
+                }
+            }
+
+            counts = next;
+        }
+
+        // the remaining iterations stay within each block
+        let mut total = Natural::zero();
+        for code in 0..counts.len() {
+            if !counts[code].is_zero() {
+                let pixels = self.expand(code, iterations % 3).count_pixels();
+                total.add_multiple(&counts[code], pixels as u32);
+            }
+        }
+        total
+    }
+}
+
+struct Options {
+    mode: Option<String>,
+    iterations: usize,
+    scale: usize,
+    format: String,
+    output: String,
+    delay: u16,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Options {
+        let mut options = Options {
+            mode: None,
+            iterations: 18,
+            scale: 1,
+            format: "png".to_string(),
+            output: ".".to_string(),
+            delay: 50,
+        };
+        let mut iterations = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scale" => {
+                    options.scale = args.next().expect("missing scale").parse().expect("invalid scale");
+                }
+                "--format" => options.format = args.next().expect("missing format"),
+                "--output" => options.output = args.next().expect("missing directory"),
+                "--delay" => {
+                    options.delay = args.next().expect("missing delay").parse().expect("invalid delay");
+                }
+                _ if options.mode.is_none() => options.mode = Some(arg),
+                _ if iterations.is_none() => {
+                    iterations = Some(arg.parse().expect("invalid iteration count"));
+                }
+                _ => panic!("unknown option: {}", arg),
+            }
+        }
+
+        options.iterations = iterations.unwrap_or(options.iterations);
+        options
+    }
+}
+
 fn main() {
     let mut input = String::new();
 
@@ -221,15 +508,56 @@ fn main() {
                              &Regex::new(r"(...)/(...)/(...) => (....)/(....)/(....)/(....)")
                                  .expect("bad regex"));
 
-    let mut map = initial_map();
+    let rules = rules2.into_iter().chain(rules3.into_iter()).collect::<Vec<_>>();
+    let (rule_table, warnings) = RuleTable::new(&rules);
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
 
-    let mut rule_set: HashMap<Bitmap, Bitmap> = HashMap::new();
-    add_to_rule_set(&mut rule_set, rules2);
-    add_to_rule_set(&mut rule_set, rules3);
+    let options = Options::parse(std::env::args().skip(1));
+    let iterations = options.iterations;
 
-    for _ in 0..18 {
-        map = map.extend(&rule_set);
-    }
+    let evolve = |count: usize| {
+        let mut frames = vec![initial_map()];
+        for _ in 0..count {
+            let next = frames.last().expect("no frames").extend(&rule_table);
+            frames.push(next);
+        }
+        frames
+    };
 
-    println!("{}", map.count_pixels());
+    match options.mode.as_ref().map(|mode| mode.as_str()) {
+        None | Some("count") => {
+            let mut evolution = BlockEvolution::new(&rule_table);
+            println!("{}", evolution.count_pixels(&initial_map(), iterations));
+        }
+        Some("print") => evolve(iterations)[iterations].print(),
+        Some("pbm") => {
+            let stdout = std::io::stdout();
+            stdout.lock().write_all(&evolve(iterations)[iterations].to_pbm(options.scale)).expect("io error");
+        }
+        Some("png") => {
+            let stdout = std::io::stdout();
+            evolve(iterations)[iterations].write_png(stdout.lock(), options.scale).expect("io error");
+        }
+        Some("gif") => {
+            let stdout = std::io::stdout();
+            write_gif(&evolve(iterations), stdout.lock(), options.scale, options.delay).expect("io error");
+        }
+        Some("frames") => {
+            for (iteration, bitmap) in evolve(iterations).iter().enumerate() {
+                let path = std::path::Path::new(&options.output)
+                    .join(format!("fractal-{:02}.{}", iteration, options.format));
+                let file = std::fs::File::create(&path).expect("cannot create image");
+                let mut writer = std::io::BufWriter::new(file);
+
+                match options.format.as_str() {
+                    "pbm" => writer.write_all(&bitmap.to_pbm(options.scale)),
+                    "png" => bitmap.write_png(&mut writer, options.scale),
+                    format => panic!("unknown format: {}", format),
+                }.expect("io error");
+            }
+        }
+        Some(mode) => panic!("unknown mode: {}", mode),
+    }
 }
@@ -0,0 +1,589 @@
+// virus carrier shared by both parts, the behaviour of each node state comes
+// from a rule table so the parts (and langton's ant style variants) differ
+// only in configuration
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Coord(pub i32, pub i32);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Turn {
+    Left,
+    Right,
+    Reverse,
+    None,
+}
+
+#[derive(Debug)]
+pub struct State {
+    pub symbol: char,
+    pub next: usize,
+    pub turn: Turn,
+}
+
+// states are referred to by index, the first one is the state of every
+// node not mentioned in the input
+#[derive(Debug)]
+pub struct RuleTable {
+    pub states: Vec<State>,
+}
+
+impl RuleTable {
+    // entries look like ". -> # left" and are separated by commas or
+    // newlines, the turn is one of left, right, reverse or none
+    pub fn parse(description: &str) -> Result<RuleTable, String> {
+        let mut entries = Vec::new();
+
+        for entry in description.split(|c| c == ',' || c == '\n') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let tokens = entry.split_whitespace().collect::<Vec<_>>();
+            if tokens.len() != 4 || tokens[1] != "->" {
+                return Err(format!("invalid rule: {}", entry));
+            }
+
+            let symbol = parse_symbol(tokens[0])?;
+            let next = parse_symbol(tokens[2])?;
+            let turn = match tokens[3] {
+                "left" => Turn::Left,
+                "right" => Turn::Right,
+                "reverse" => Turn::Reverse,
+                "none" => Turn::None,
+                turn => return Err(format!("invalid turn: {}", turn)),
+            };
+
+            if entries.iter().any(|&(other, _, _)| other == symbol) {
+                return Err(format!("duplicate rule for {}", symbol));
+            }
+            entries.push((symbol, next, turn));
+        }
+
+        if entries.is_empty() {
+            return Err("no rules".to_string());
+        }
+        if entries.len() > 256 {
+            return Err("too many states".to_string());
+        }
+
+        let mut states = Vec::new();
+        for &(symbol, next, turn) in &entries {
+            let next = entries.iter()
+                .position(|&(other, _, _)| other == next)
+                .ok_or_else(|| format!("no rule for {}", next))?;
+            states.push(State {
+                symbol: symbol,
+                next: next,
+                turn: turn,
+            });
+        }
+
+        Ok(RuleTable { states: states })
+    }
+
+    pub fn state(&self, symbol: char) -> Option<usize> {
+        self.states.iter().position(|state| state.symbol == symbol)
+    }
+}
+
+fn parse_symbol(token: &str) -> Result<char, String> {
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(symbol), None) => Ok(symbol),
+        _ => Err(format!("invalid state symbol: {}", token)),
+    }
+}
+
+// node states by index, nodes never set are in state 0
+pub trait Grid {
+    fn get(&self, pos: Coord) -> u8;
+    fn set(&mut self, pos: Coord, state: u8);
+    // every node not in state 0
+    fn nodes(&self) -> Vec<(Coord, u8)>;
+}
+
+pub struct HashGrid {
+    nodes: HashMap<Coord, u8>,
+}
+
+impl HashGrid {
+    pub fn new() -> HashGrid {
+        HashGrid { nodes: HashMap::new() }
+    }
+}
+
+impl Grid for HashGrid {
+    fn get(&self, pos: Coord) -> u8 {
+        *self.nodes.get(&pos).unwrap_or(&0)
+    }
+
+    fn set(&mut self, pos: Coord, state: u8) {
+        if state == 0 {
+            self.nodes.remove(&pos);
+        } else {
+            self.nodes.insert(pos, state);
+        }
+    }
+
+    fn nodes(&self) -> Vec<(Coord, u8)> {
+        self.nodes.iter().map(|(&pos, &state)| (pos, state)).collect()
+    }
+}
+
+// rectangle of nodes around the origin, doubling its size towards any
+// side the carrier walks off
+pub struct DenseGrid {
+    cells: Vec<u8>,
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+}
+
+impl DenseGrid {
+    pub fn new() -> DenseGrid {
+        DenseGrid {
+            cells: vec![0; 16 * 16],
+            left: -8,
+            top: -8,
+            width: 16,
+            height: 16,
+        }
+    }
+
+    fn index(&self, pos: Coord) -> Option<usize> {
+        let x = pos.0 - self.left;
+        let y = pos.1 - self.top;
+        if x >= 0 && x < self.width && y >= 0 && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn grow(&mut self, pos: Coord) {
+        let (mut left, mut width) = (self.left, self.width);
+        while pos.0 < left {
+            left -= width;
+            width *= 2;
+        }
+        while pos.0 >= left + width {
+            width *= 2;
+        }
+
+        let (mut top, mut height) = (self.top, self.height);
+        while pos.1 < top {
+            top -= height;
+            height *= 2;
+        }
+        while pos.1 >= top + height {
+            height *= 2;
+        }
+
+        let mut cells = vec![0; (width * height) as usize];
+        for y in 0..self.height {
+            let src = (y * self.width) as usize;
+            let dst = ((y + self.top - top) * width + self.left - left) as usize;
+            cells[dst..dst + self.width as usize].copy_from_slice(&self.cells[src..src + self.width as usize]);
+        }
+
+        self.cells = cells;
+        self.left = left;
+        self.top = top;
+        self.width = width;
+        self.height = height;
+    }
+}
+
+impl Grid for DenseGrid {
+    fn get(&self, pos: Coord) -> u8 {
+        self.index(pos).map_or(0, |index| self.cells[index])
+    }
+
+    fn set(&mut self, pos: Coord, state: u8) {
+        let index = match self.index(pos) {
+            Some(index) => index,
+            None => {
+                self.grow(pos);
+                self.index(pos).expect("grid did not grow")
+            }
+        };
+        self.cells[index] = state;
+    }
+
+    fn nodes(&self) -> Vec<(Coord, u8)> {
+        self.cells.iter()
+            .enumerate()
+            .filter(|&(_, &state)| state != 0)
+            .map(|(index, &state)| {
+                let index = index as i32;
+                (Coord(self.left + index % self.width, self.top + index / self.width), state)
+            })
+            .collect()
+    }
+}
+
+pub fn load_grid<G: Grid>(input: &str, rules: &RuleTable, grid: &mut G) {
+    let rows = input.split_whitespace().collect::<Vec<&str>>();
+    let row_count = rows.len() as i32;
+
+    for (y, row) in rows.iter().enumerate() {
+        let column_count = row.chars().count() as i32;
+
+        for (x, ch) in row.chars().enumerate() {
+            match rules.state(ch) {
+                Some(state) => {
+                    grid.set(Coord(x as i32 - column_count / 2, y as i32 - row_count / 2), state as u8);
+                }
+                None => {
+                    panic!("invalid input ({})", ch);
+                }
+            };
+        }
+    }
+}
+
+pub struct Carrier<'a, G: Grid> {
+    rules: &'a RuleTable,
+    grid: G,
+    pos: Coord,
+    dir: Direction,
+}
+
+impl<'a, G: Grid> Carrier<'a, G> {
+    pub fn new(rules: &'a RuleTable, grid: G) -> Carrier<'a, G> {
+        Carrier {
+            rules: rules,
+            grid: grid,
+            pos: Coord(0, 0),
+            dir: Direction::Up,
+        }
+    }
+
+    // turns, updates the current node and moves on, returns the state the
+    // node ended up in
+    pub fn burst(&mut self) -> usize {
+        let state = &self.rules.states[self.grid.get(self.pos) as usize];
+
+        self.dir = turn(self.dir, state.turn);
+        self.grid.set(self.pos, state.next as u8);
+        self.pos = advance(self.pos, self.dir);
+
+        state.next
+    }
+
+    pub fn snapshot(&self, num_bursts: usize) -> Snapshot {
+        Snapshot {
+            num_bursts: num_bursts,
+            pos: self.pos,
+            nodes: self.grid.nodes(),
+        }
+    }
+}
+
+// state of the grid and carrier after some bursts, independent of the grid
+// backend
+pub struct Snapshot {
+    pub num_bursts: usize,
+    pub pos: Coord,
+    pub nodes: Vec<(Coord, u8)>,
+}
+
+impl Snapshot {
+    // smallest rectangle holding the carrier and every node not in state 0,
+    // as (left, top, right, bottom) inclusive
+    pub fn bounds(&self) -> (i32, i32, i32, i32) {
+        self.nodes.iter().fold((self.pos.0, self.pos.1, self.pos.0, self.pos.1), |bounds, &(pos, _)| {
+            (std::cmp::min(bounds.0, pos.0),
+             std::cmp::min(bounds.1, pos.1),
+             std::cmp::max(bounds.2, pos.0),
+             std::cmp::max(bounds.3, pos.1))
+        })
+    }
+
+    fn states(&self) -> HashMap<Coord, u8> {
+        self.nodes.iter().cloned().collect()
+    }
+
+    // puzzle notation: nodes separated by spaces, the carrier's node in
+    // brackets
+    pub fn to_ascii(&self, rules: &RuleTable) -> String {
+        let (left, top, right, bottom) = self.bounds();
+        let states = self.states();
+        let mut output = String::new();
+
+        for y in top..bottom + 1 {
+            for x in left..right + 1 {
+                if Coord(x, y) == self.pos {
+                    output.push('[');
+                } else if Coord(x - 1, y) == self.pos {
+                    output.push(']');
+                } else if x > left {
+                    output.push(' ');
+                }
+                let state = *states.get(&Coord(x, y)).unwrap_or(&0);
+                output.push(rules.states[state as usize].symbol);
+            }
+            if Coord(right, y) == self.pos {
+                output.push(']');
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+
+    // one pixel per node with a one node border, the carrier is black
+    pub fn to_ppm(&self, rules: &RuleTable) -> Vec<u8> {
+        let (left, top, right, bottom) = self.bounds();
+        let (left, top, right, bottom) = (left - 1, top - 1, right + 1, bottom + 1);
+        let states = self.states();
+
+        let mut output = format!("P6\n{} {}\n255\n", right - left + 1, bottom - top + 1).into_bytes();
+        for y in top..bottom + 1 {
+            for x in left..right + 1 {
+                let color = if Coord(x, y) == self.pos {
+                    [0, 0, 0]
+                } else {
+                    let state = *states.get(&Coord(x, y)).unwrap_or(&0) as usize;
+                    state_color(rules.states[state].symbol, state)
+                };
+                output.extend(color.iter());
+            }
+        }
+
+        output
+    }
+
+    pub fn summary(&self, rules: &RuleTable) -> String {
+        let (left, top, right, bottom) = self.bounds();
+        let mut counts = vec![0; rules.states.len()];
+        for &(_, state) in &self.nodes {
+            counts[state as usize] += 1;
+        }
+
+        let mut output = format!("bursts: {}\ncarrier: {},{}\nbounds: {},{} to {},{} ({}x{})\n",
+                                 self.num_bursts,
+                                 self.pos.0,
+                                 self.pos.1,
+                                 left,
+                                 top,
+                                 right,
+                                 bottom,
+                                 right - left + 1,
+                                 bottom - top + 1);
+        for (state, count) in rules.states.iter().zip(counts.iter()).skip(1) {
+            output.push_str(&format!("{}: {}\n", state.symbol, count));
+        }
+        output
+    }
+}
+
+// puzzle states get fixed colors, anything else one from a palette
+fn state_color(symbol: char, state: usize) -> [u8; 3] {
+    const PALETTE: [[u8; 3]; 6] = [
+        [0x66, 0xc2, 0xa5],
+        [0xfc, 0x8d, 0x62],
+        [0x8d, 0xa0, 0xcb],
+        [0xe7, 0x8a, 0xc3],
+        [0xa6, 0xd8, 0x54],
+        [0xff, 0xd9, 0x2f],
+    ];
+
+    match symbol {
+        '.' => [0xff, 0xff, 0xff],
+        'W' => [0xff, 0xc0, 0x40],
+        '#' => [0xd0, 0x20, 0x20],
+        'F' => [0x30, 0x60, 0xd0],
+        _ => PALETTE[state % PALETTE.len()],
+    }
+}
+
+// number of bursts that leave a node in the given state, with a snapshot
+// passed to report at the start, every given number of bursts and at the
+// end (unless every is 0)
+pub fn count_infections<G: Grid, F: FnMut(&Snapshot)>(carrier: &mut Carrier<G>,
+                                                     infected: usize,
+                                                     num_bursts: usize,
+                                                     every: usize,
+                                                     mut report: F) -> usize {
+    let mut num_infections = 0;
+    let mut done = 0;
+
+    loop {
+        if every > 0 {
+            report(&carrier.snapshot(done));
+        }
+        if done == num_bursts {
+            break;
+        }
+
+        let chunk = if every > 0 { std::cmp::min(every, num_bursts - done) } else { num_bursts - done };
+        num_infections += (0..chunk).filter(|_| carrier.burst() == infected).count();
+        done += chunk;
+    }
+
+    num_infections
+}
+
+struct Options {
+    rules: String,
+    infected: char,
+    num_bursts: usize,
+    backend: String,
+    every: Option<usize>,
+    ascii: bool,
+    summary: bool,
+    ppm_directory: Option<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I, rules: &str, num_bursts: usize) -> Options {
+        let mut options = Options {
+            rules: rules.to_string(),
+            infected: '#',
+            num_bursts: num_bursts,
+            backend: "dense".to_string(),
+            every: None,
+            ascii: false,
+            summary: false,
+            ppm_directory: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rules" => options.rules = args.next().expect("missing rules"),
+                "--backend" => options.backend = args.next().expect("missing backend"),
+                "--infected" => {
+                    options.infected = args.next().expect("missing symbol").chars().next().expect("empty symbol");
+                }
+                "--every" => {
+                    options.every = Some(args.next().expect("missing count").parse().expect("invalid count"));
+                }
+                "--ascii" => options.ascii = true,
+                "--summary" => options.summary = true,
+                "--ppm" => options.ppm_directory = Some(args.next().expect("missing directory")),
+                _ => options.num_bursts = arg.parse().expect("invalid burst count"),
+            }
+        }
+        options
+    }
+
+    // snapshots are taken only when some output asks for them, by default
+    // just before and after the run
+    fn snapshot_period(&self) -> usize {
+        if self.ascii || self.summary || self.ppm_directory.is_some() {
+            self.every.unwrap_or(std::cmp::max(self.num_bursts, 1))
+        } else {
+            0
+        }
+    }
+}
+
+// loads the input into the given grid and runs the carrier on it
+fn run<G: Grid>(mut grid: G, input: &str, rules: &RuleTable, options: &Options) -> usize {
+    load_grid(input, rules, &mut grid);
+    let infected = rules.state(options.infected).expect("no rule for infected symbol");
+    let mut carrier = Carrier::new(rules, grid);
+
+    count_infections(&mut carrier, infected, options.num_bursts, options.snapshot_period(), |snapshot| {
+        if options.summary {
+            print!("{}", snapshot.summary(rules));
+        }
+        if options.ascii {
+            print!("{}", snapshot.to_ascii(rules));
+        }
+        if options.summary || options.ascii {
+            println!();
+        }
+        if let Some(ref directory) = options.ppm_directory {
+            let path = std::path::Path::new(directory).join(format!("grid-{:08}.ppm", snapshot.num_bursts));
+            let mut file = std::fs::File::create(&path).expect("cannot create snapshot");
+            file.write_all(&snapshot.to_ppm(rules)).expect("io error");
+        }
+    })
+}
+
+// entry point of both parts, which only differ in their default rules and
+// number of bursts
+pub fn main(rules: &str, num_bursts: usize) {
+    let options = Options::parse(std::env::args().skip(1), rules, num_bursts);
+    let rules = RuleTable::parse(&options.rules).unwrap_or_else(|e| panic!("{}", e));
+
+    let mut input = String::new();
+
+    std::io::stdin().read_to_string(&mut input).expect("io error");
+
+    let num_infections = match options.backend.as_str() {
+        "dense" => run(DenseGrid::new(), &input, &rules, &options),
+        "hash" => run(HashGrid::new(), &input, &rules, &options),
+        // runs both backends, timing them and checking they agree
+        "bench" => {
+            let start = Instant::now();
+            let dense = run(DenseGrid::new(), &input, &rules, &options);
+            let dense_time = start.elapsed();
+
+            let start = Instant::now();
+            let hash = run(HashGrid::new(), &input, &rules, &options);
+            let hash_time = start.elapsed();
+
+            let millis = |time: Duration| time.as_secs() * 1000 + time.subsec_nanos() as u64 / 1000000;
+            eprintln!("dense: {} ms", millis(dense_time));
+            eprintln!("hash: {} ms", millis(hash_time));
+
+            assert!(dense == hash, "backends disagree: {} vs {}", dense, hash);
+            dense
+        }
+        backend => panic!("unknown backend: {}", backend),
+    };
+    println!("{}", num_infections);
+}
+
+fn turn(dir: Direction, turn: Turn) -> Direction {
+    match turn {
+        Turn::Left => turn_left(dir),
+        Turn::Right => turn_right(dir),
+        Turn::Reverse => turn_left(turn_left(dir)),
+        Turn::None => dir,
+    }
+}
+
+fn turn_left(dir: Direction) -> Direction {
+    match dir {
+        Direction::Up => Direction::Left,
+        Direction::Right => Direction::Up,
+        Direction::Down => Direction::Right,
+        Direction::Left => Direction::Down,
+    }
+}
+
+fn turn_right(dir: Direction) -> Direction {
+    match dir {
+        Direction::Up => Direction::Right,
+        Direction::Right => Direction::Down,
+        Direction::Down => Direction::Left,
+        Direction::Left => Direction::Up,
+    }
+}
+
+fn advance(pos: Coord, dir: Direction) -> Coord {
+    match dir {
+        Direction::Up => Coord(pos.0, pos.1 - 1),
+        Direction::Right => Coord(pos.0 + 1, pos.1),
+        Direction::Down => Coord(pos.0, pos.1 + 1),
+        Direction::Left => Coord(pos.0 - 1, pos.1),
+    }
+}
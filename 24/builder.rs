@@ -0,0 +1,367 @@
+// bridge search shared by both parts, the best bridge is picked by an
+// objective so the parts differ only in which one they use
+
+use std::cmp::Reverse;
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+pub struct Component {
+    pub port1: usize,
+    pub port2: usize,
+}
+
+impl Component {
+    fn strength(&self) -> usize {
+        self.port1 + self.port2
+    }
+}
+
+pub fn load_components(reader: &mut BufRead) -> Vec<Component> {
+    let mut components = Vec::new();
+
+    for line in reader.lines() {
+        let line = line.expect("io error");
+
+        let ports = line.split('/')
+            .map(|token| token.trim().parse::<usize>().expect("invalid port"))
+            .collect::<Vec<_>>();
+
+        if ports.len() == 2 {
+            components.push(Component {
+                port1: ports[0],
+                port2: ports[1],
+            });
+        }
+    }
+
+    components
+}
+
+// everything objectives get to know about a bridge, which only depends on
+// the set of components used
+#[derive(Clone, Copy, Debug)]
+pub struct Bridge {
+    pub length: usize,
+    pub strength: usize,
+}
+
+pub trait Objective {
+    type Score: Ord;
+
+    // None if the bridge doesn't qualify at all
+    fn score(&self, bridge: &Bridge) -> Option<Self::Score>;
+
+    // at least the score of the bridge and of anything built by adding at
+    // most spare.length components of spare.strength total strength to it,
+    // None if none of those can qualify
+    fn bound(&self, bridge: &Bridge, spare: &Bridge) -> Option<Self::Score>;
+
+    // whether any way of extending the second bridge scores no better when
+    // used to extend the first one instead
+    fn dominates(&self, bridge: &Bridge, other: &Bridge) -> bool;
+}
+
+pub struct Strongest;
+
+impl Objective for Strongest {
+    type Score = usize;
+
+    fn score(&self, bridge: &Bridge) -> Option<usize> {
+        Some(bridge.strength)
+    }
+
+    fn bound(&self, bridge: &Bridge, spare: &Bridge) -> Option<usize> {
+        Some(bridge.strength + spare.strength)
+    }
+
+    fn dominates(&self, bridge: &Bridge, other: &Bridge) -> bool {
+        bridge.strength >= other.strength
+    }
+}
+
+pub struct LongestThenStrongest;
+
+impl Objective for LongestThenStrongest {
+    type Score = (usize, usize);
+
+    fn score(&self, bridge: &Bridge) -> Option<(usize, usize)> {
+        Some((bridge.length, bridge.strength))
+    }
+
+    fn bound(&self, bridge: &Bridge, spare: &Bridge) -> Option<(usize, usize)> {
+        Some((bridge.length + spare.length, bridge.strength + spare.strength))
+    }
+
+    fn dominates(&self, bridge: &Bridge, other: &Bridge) -> bool {
+        bridge.length >= other.length && bridge.strength >= other.strength
+    }
+}
+
+// fewest components reaching the target strength, the strongest of those
+pub struct FewestReaching(pub usize);
+
+impl Objective for FewestReaching {
+    type Score = (Reverse<usize>, usize);
+
+    fn score(&self, bridge: &Bridge) -> Option<(Reverse<usize>, usize)> {
+        if bridge.strength >= self.0 {
+            Some((Reverse(bridge.length), bridge.strength))
+        } else {
+            None
+        }
+    }
+
+    fn bound(&self, bridge: &Bridge, spare: &Bridge) -> Option<(Reverse<usize>, usize)> {
+        if bridge.strength >= self.0 {
+            // adding components only makes it worse
+            self.score(bridge)
+        } else if bridge.strength + spare.strength >= self.0 {
+            Some((Reverse(bridge.length + 1), bridge.strength + spare.strength))
+        } else {
+            None
+        }
+    }
+
+    fn dominates(&self, bridge: &Bridge, other: &Bridge) -> bool {
+        bridge.length <= other.length && bridge.strength >= other.strength
+    }
+}
+
+// number of remembered search states, a lossy table so memory stays fixed
+const MEMO_SIZE: usize = 1 << 20;
+
+// depth first search over bridges starting at port 0; bridges ending in the
+// same port with the same unused components reachable from it can be
+// extended the same ways, so the search remembers the bridges it expanded
+// by a hash of both (xor of per component and per port random keys) and
+// skips bridges dominated by the one remembered
+struct Search<'a, O: Objective + 'a> {
+    components: &'a [Component],
+    // components by port, strongest first so good bridges are found early
+    by_port: Vec<Vec<usize>>,
+    objective: &'a O,
+    used: Vec<bool>,
+    component_keys: Vec<u64>,
+    port_keys: Vec<u64>,
+    memo: Vec<(u64, Bridge)>,
+    // scratch marks for finding the components reachable from a port
+    stamp: u32,
+    port_stamps: Vec<u32>,
+    component_stamps: Vec<u32>,
+    stack: Vec<usize>,
+    best: Option<(O::Score, Bridge)>,
+}
+
+impl<'a, O: Objective> Search<'a, O> {
+    fn new(components: &'a [Component], objective: &'a O) -> Search<'a, O> {
+        let num_ports = components.iter().map(|component| component.port1.max(component.port2) + 1).max().unwrap_or(0);
+        let mut by_port = vec![Vec::new(); std::cmp::max(num_ports, 1)];
+        for (index, component) in components.iter().enumerate() {
+            by_port[component.port1].push(index);
+            if component.port2 != component.port1 {
+                by_port[component.port2].push(index);
+            }
+        }
+        for candidates in by_port.iter_mut() {
+            candidates.sort_by_key(|&index| Reverse(components[index].strength()));
+        }
+
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+
+        Search {
+            components: components,
+            objective: objective,
+            used: vec![false; components.len()],
+            component_keys: (0..components.len()).map(|_| random.next()).collect(),
+            port_keys: (0..by_port.len()).map(|_| random.next()).collect(),
+            memo: vec![(0, Bridge { length: 0, strength: 0 }); MEMO_SIZE],
+            stamp: 0,
+            port_stamps: vec![0; by_port.len()],
+            component_stamps: vec![0; components.len()],
+            stack: Vec::new(),
+            by_port: by_port,
+            best: None,
+        }
+    }
+
+    // returns whether a bridge dominating this one was expanded from the
+    // same state, remembering this one otherwise
+    fn check_memo(&mut self, bridge: &Bridge, port: usize, reachable_key: u64) -> bool {
+        // 0 marks an empty slot
+        let key = (reachable_key ^ self.port_keys[port]) | 1;
+        let slot = (key >> 1) as usize % MEMO_SIZE;
+        if self.memo[slot].0 == key && self.objective.dominates(&self.memo[slot].1, bridge) {
+            return true;
+        }
+        self.memo[slot] = (key, *bridge);
+        false
+    }
+
+    // count and total strength of the unused components reachable from the
+    // port, no extension of the bridge can use any others, and the key of
+    // their set
+    fn spare(&mut self, port: usize) -> (Bridge, u64) {
+        self.stamp += 1;
+        let stamp = self.stamp;
+        let mut spare = Bridge { length: 0, strength: 0 };
+        let mut key = 0;
+        let mut stack = std::mem::replace(&mut self.stack, Vec::new());
+        stack.push(port);
+        self.port_stamps[port] = stamp;
+
+        while let Some(port) = stack.pop() {
+            for &index in &self.by_port[port] {
+                if self.used[index] || self.component_stamps[index] == stamp {
+                    continue;
+                }
+                self.component_stamps[index] = stamp;
+
+                let component = &self.components[index];
+                spare.length += 1;
+                spare.strength += component.strength();
+                key ^= self.component_keys[index];
+
+                for &next_port in &[component.port1, component.port2] {
+                    if self.port_stamps[next_port] != stamp {
+                        self.port_stamps[next_port] = stamp;
+                        stack.push(next_port);
+                    }
+                }
+            }
+        }
+
+        self.stack = stack;
+        (spare, key)
+    }
+
+    fn visit(&mut self, bridge: Bridge, port: usize) {
+        if let Some(score) = self.objective.score(&bridge) {
+            if self.best.as_ref().map_or(true, |&(ref best, _)| score > *best) {
+                self.best = Some((score, bridge));
+            }
+        }
+
+        let (spare, reachable_key) = self.spare(port);
+        if self.check_memo(&bridge, port, reachable_key) {
+            return;
+        }
+
+        match (self.objective.bound(&bridge, &spare), &self.best) {
+            (None, _) => return,
+            (Some(ref bound), &Some((ref best, _))) if bound <= best => return,
+            _ => {}
+        }
+
+        for position in 0..self.by_port[port].len() {
+            let index = self.by_port[port][position];
+            if self.used[index] {
+                continue;
+            }
+
+            let (next_port, strength) = {
+                let component = &self.components[index];
+                let next_port = if component.port1 == port { component.port2 } else { component.port1 };
+                (next_port, component.strength())
+            };
+            let extended = Bridge {
+                length: bridge.length + 1,
+                strength: bridge.strength + strength,
+            };
+
+            self.used[index] = true;
+            self.visit(extended, next_port);
+            self.used[index] = false;
+        }
+    }
+}
+
+// xorshift generator for the memo keys and benchmark inputs
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+pub fn find_best<O: Objective>(components: &[Component], objective: &O) -> Option<Bridge> {
+    let mut search = Search::new(components, objective);
+    search.visit(Bridge { length: 0, strength: 0 }, 0);
+    search.best.map(|(_, bridge)| bridge)
+}
+
+// components with random ports up to 50 like the puzzle input, the first
+// one fits the zero port
+fn random_components(count: usize, seed: u64) -> Vec<Component> {
+    let mut random = Random(seed);
+    (0..count)
+        .map(|index| {
+            Component {
+                port1: if index == 0 { 0 } else { (random.next() % 51) as usize },
+                port2: (random.next() % 51) as usize,
+            }
+        })
+        .collect()
+}
+
+// times the search on generated inputs larger than the puzzle's
+fn benchmark(count: usize) {
+    for seed in 1..4 {
+        let components = random_components(count, seed);
+
+        let start = Instant::now();
+        let strongest = find_best(&components, &Strongest).expect("no bridge");
+        let strongest_time = start.elapsed();
+
+        let start = Instant::now();
+        let longest = find_best(&components, &LongestThenStrongest).expect("no bridge");
+        let longest_time = start.elapsed();
+
+        let millis = |time: Duration| time.as_secs() * 1000 + time.subsec_nanos() as u64 / 1000000;
+        println!("seed {}: strongest {} in {} ms, longest {} ({}) in {} ms",
+                 seed,
+                 strongest.strength,
+                 millis(strongest_time),
+                 longest.strength,
+                 longest.length,
+                 millis(longest_time));
+    }
+}
+
+// entry point of both parts, the objective can be picked on the command
+// line as strongest, longest or fewest followed by a strength; bench
+// followed by a component count runs the search on generated inputs
+pub fn main(default_objective: &str) {
+    let mut args = std::env::args().skip(1);
+    let objective = args.next().unwrap_or_else(|| default_objective.to_string());
+
+    if objective == "bench" {
+        benchmark(args.next().map_or(64, |arg| arg.parse().expect("invalid component count")));
+        return;
+    }
+
+    let stdin = std::io::stdin();
+    let components = load_components(&mut stdin.lock());
+
+    match objective.as_str() {
+        "strongest" => {
+            let bridge = find_best(&components, &Strongest).expect("no bridge");
+            println!("{}", bridge.strength);
+        }
+        "longest" => {
+            let bridge = find_best(&components, &LongestThenStrongest).expect("no bridge");
+            println!("{}", bridge.strength);
+        }
+        "fewest" => {
+            let target = args.next().expect("missing strength").parse().expect("invalid strength");
+            match find_best(&components, &FewestReaching(target)) {
+                Some(bridge) => println!("{} ({})", bridge.length, bridge.strength),
+                None => println!("none"),
+            }
+        }
+        objective => panic!("unknown objective: {}", objective),
+    }
+}
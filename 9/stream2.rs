@@ -1,97 +1,362 @@
-#![feature(try_trait)]
-
+use std::collections::BTreeMap;
+use std::fmt;
 use std::io::Read;
 use std::iter::Peekable;
 
+#[derive(Clone, Copy, Debug)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    fn new() -> Position {
+        Position { offset: 0, line: 1, column: 1 }
+    }
+
+    fn advance(&mut self, ch: char) {
+        self.offset += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    // for raw bytes, columns count characters so continuation bytes of a
+    // multibyte character don't start a new one
+    fn advance_byte(&mut self, byte: u8) {
+        self.offset += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if byte & 0xc0 != 0x80 {
+            self.column += 1;
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {} (offset {})", self.line, self.column, self.offset)
+    }
+}
+
 #[derive(Debug)]
 enum Error {
-    UnexpectedEof,
-    UnexpectedChar(char, String),
+    UnexpectedEof(Position),
+    UnexpectedChar(char, String, Position),
+    Io(std::io::Error),
+}
+
+impl Error {
+    // formats the error followed by the offending line and a caret under
+    // the reported column
+    fn report(&self, source: &str) -> String {
+        let position = match *self {
+            Error::UnexpectedEof(position) | Error::UnexpectedChar(_, _, position) => position,
+            Error::Io(_) => return self.to_string(),
+        };
+        let line = source.lines().nth(position.line - 1).unwrap_or("");
+        let caret_pos = line.chars().take(position.column - 1).map(|ch| ch.len_utf8()).sum::<usize>();
+
+        format!("{}\n{}\n{}^", self, line, " ".repeat(caret_pos))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnexpectedEof(position) => write!(f, "{}: unexpected end of stream", position),
+            Error::UnexpectedChar(ch, ref expected_chars, position) if expected_chars.is_empty() => {
+                write!(f, "{}: unexpected {:?}, expected end of stream", position, ch)
+            }
+            Error::UnexpectedChar(ch, ref expected_chars, position) => {
+                write!(f, "{}: unexpected {:?}, expected one of {:?}", position, ch, expected_chars)
+            }
+            Error::Io(ref e) => write!(f, "io error: {}", e),
+        }
+    }
 }
 
-impl From<std::option::NoneError> for Error {
-    fn from(_e: std::option::NoneError) -> Error {
-        Error::UnexpectedEof
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
     }
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+#[derive(Debug)]
+enum Node {
+    Group { children: Vec<Node> },
+    // raw holds the text between '<' and '>' verbatim, canceled_positions
+    // are byte offsets into raw of the characters following each '!'
+    Garbage { raw: String, canceled_positions: Vec<usize> },
+}
+
+impl Node {
+    // visits nodes depth-first, groups at the outermost level have depth 1
+    // and garbage shares the depth of its enclosing group
+    fn fold<T, F>(&self, init: T, f: &mut F) -> T
+        where F: FnMut(T, &Node, u32) -> T
+    {
+        self.fold_at(init, f, 1)
+    }
+
+    fn fold_at<T, F>(&self, init: T, f: &mut F, depth: u32) -> T
+        where F: FnMut(T, &Node, u32) -> T
+    {
+        let mut acc = f(init, self, depth);
+        if let Node::Group { ref children } = *self {
+            for child in children {
+                let child_depth = match *child {
+                    Node::Group { .. } => depth + 1,
+                    Node::Garbage { .. } => depth,
+                };
+                acc = child.fold_at(acc, f, child_depth);
+            }
+        }
+        acc
+    }
+
+    fn group_score(&self) -> u32 {
+        self.fold(0, &mut |score, node, depth| {
+            match *node {
+                Node::Group { .. } => score + depth,
+                Node::Garbage { .. } => score,
+            }
+        })
+    }
+
+    fn garbage_count(&self) -> usize {
+        self.fold(0, &mut |count, node, _depth| {
+            match *node {
+                Node::Group { .. } => count,
+                Node::Garbage { .. } => count + node.cleaned_garbage().chars().count(),
+            }
+        })
+    }
+
+    fn depth_histogram(&self) -> BTreeMap<u32, u32> {
+        self.fold(BTreeMap::new(), &mut |mut histogram, node, depth| {
+            if let Node::Group { .. } = *node {
+                *histogram.entry(depth).or_insert(0) += 1;
+            }
+            histogram
+        })
+    }
+
+    // returns the group with the most direct children along with its depth
+    fn largest_group(&self) -> (&Node, u32) {
+        let mut largest = (self, 1);
+
+        fn visit<'a>(node: &'a Node, depth: u32, largest: &mut (&'a Node, u32)) {
+            if let Node::Group { ref children } = *node {
+                if children.len() > largest.0.num_children() {
+                    *largest = (node, depth);
+                }
+                for child in children {
+                    visit(child, depth + 1, largest);
+                }
+            }
+        }
+
+        visit(self, 1, &mut largest);
+
+        largest
+    }
+
+    fn num_children(&self) -> usize {
+        match *self {
+            Node::Group { ref children } => children.len(),
+            Node::Garbage { .. } => 0,
+        }
+    }
+
+    fn cleaned_garbage(&self) -> String {
+        match *self {
+            Node::Group { .. } => String::new(),
+            Node::Garbage { ref raw, ref canceled_positions } => {
+                // canceled positions are in increasing order
+                let mut canceled = canceled_positions.iter().peekable();
+                raw.char_indices()
+                    .filter(|&(pos, ch)| {
+                        if canceled.peek() == Some(&&pos) {
+                            canceled.next();
+                            return false;
+                        }
+                        ch != '!'
+                    })
+                    .map(|(_, ch)| ch)
+                    .collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Node::Group { ref children } => {
+                write!(f, "{{")?;
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", child)?;
+                }
+                write!(f, "}}")
+            }
+            Node::Garbage { ref raw, .. } => write!(f, "<{}>", raw),
+        }
+    }
+}
+
 struct Parser<'a> {
     stream: &'a mut Peekable<std::str::Chars<'a>>,
-    group_level: u32,
-    group_score: u32,
-    garbage_count: u32,
+    position: Position,
+    // when set, errors inside groups are collected instead of returned and
+    // parsing resumes at the next ',' or '}'
+    recover: bool,
+    errors: Vec<Error>,
 }
 
 impl<'a> Parser<'a> {
-    fn new(stream: &'a mut Peekable<std::str::Chars<'a>>) -> Parser<'a> {
+    fn new(stream: &'a mut Peekable<std::str::Chars<'a>>, recover: bool) -> Parser<'a> {
         Parser {
             stream: stream,
-            group_level: 0,
-            group_score: 0,
-            garbage_count: 0,
+            position: Position::new(),
+            recover: recover,
+            errors: Vec::new(),
         }
     }
 
-    fn parse(&mut self) -> Result<()> {
-        self.parse_group()?;
-        Ok(())
+    // the stream holds a single group, only whitespace may follow it; when
+    // recovering, anything else is reported once and the rest ignored
+    fn parse(&mut self) -> Result<Node> {
+        let root = self.parse_group()?;
+        while self.stream.peek().map_or(false, |ch| ch.is_whitespace()) {
+            self.next_char()?;
+        }
+
+        if let Some(ch) = self.stream.peek().map(|ch| *ch) {
+            let error = Error::UnexpectedChar(ch, String::new(), self.position);
+            self.recover_from(error)?;
+            while self.stream.next().is_some() {}
+        }
+        Ok(root)
     }
 
-    fn parse_compound(&mut self) -> Result<()> {
+    fn parse_compound(&mut self) -> Result<Vec<Node>> {
+        let mut children = Vec::new();
         loop {
-            match self.peek_char("{<")? {
-                '{' => { self.parse_group()?; }
-                '<' => { self.parse_garbage()?; }
-                _ => { assert!(false); }
+            match self.parse_item() {
+                Ok(child) => { children.push(child); }
+                Err(e) => {
+                    self.recover_from(e)?;
+                    if self.peek_char("{<").is_some() {
+                        continue;
+                    }
+                }
+            }
+
+            if self.accept_char(",").is_some() {
+                continue;
             }
 
-            if self.accept_char(",").is_none() {
-                return Ok(())
+            match self.stream.peek().map(|ch| *ch) {
+                None | Some('}') => { return Ok(children) }
+                Some(ch) => {
+                    let error = Error::UnexpectedChar(ch, String::from(",}"), self.position);
+                    self.recover_from(error)?;
+                    if self.peek_char("{<").is_none() && self.accept_char(",").is_none() {
+                        return Ok(children)
+                    }
+                }
             }
         }
     }
 
-    fn parse_group(&mut self) -> Result<()> {
-        self.group_level += 1;
-        self.group_score += self.group_level;
+    fn parse_item(&mut self) -> Result<Node> {
+        match self.stream.peek().map(|ch| *ch) {
+            Some('{') => self.parse_group(),
+            Some('<') => self.parse_garbage(),
+            Some(ch) => Err(Error::UnexpectedChar(ch, String::from("{<"), self.position)),
+            None => Err(Error::UnexpectedEof(self.position)),
+        }
+    }
 
+    fn parse_group(&mut self) -> Result<Node> {
         self.expect_char("{")?;
-        if self.peek_char("}").is_none() {
-            self.parse_compound()?;
-        }
+        let children = if self.peek_char("}").is_none() {
+            self.parse_compound()?
+        } else {
+            Vec::new()
+        };
         self.expect_char("}")?;
 
-        self.group_level -= 1;
-        Ok(())
+        Ok(Node::Group { children: children })
     }
 
-    fn parse_garbage(&mut self) -> Result<()> {
+    fn parse_garbage(&mut self) -> Result<Node> {
+        let mut raw = String::new();
+        let mut canceled_positions = Vec::new();
+
         self.expect_char("<")?;
         loop {
-            match self.stream.next()? {
-                '>' => { return Ok(()) }
-                '!' => { self.stream.next()?; }
-                _ => { self.garbage_count += 1; }
+            match self.next_char()? {
+                '>' => {
+                    return Ok(Node::Garbage { raw: raw, canceled_positions: canceled_positions })
+                }
+                '!' => {
+                    raw.push('!');
+                    canceled_positions.push(raw.len());
+                    raw.push(self.next_char()?);
+                }
+                ch => { raw.push(ch); }
             }
         }
     }
 
-    fn expect_char(&mut self, expected_chars: &str) -> Result<char> {
-        if let Some(ch) = self.expect_char_or_eof(expected_chars)? {
-            Ok(ch)
-        } else {
-            Err(Error::UnexpectedEof)
+    // skips input up to the next ',' or '}' without consuming it, or up to a
+    // '{' or '<' which the caller parses as another item so groups and
+    // garbage following the error are neither lost nor torn apart; errors at
+    // the end of the stream can't be recovered from
+    fn recover_from(&mut self, error: Error) -> Result<()> {
+        if !self.recover {
+            return Err(error);
+        }
+        if let Error::UnexpectedEof(_) = error {
+            return Err(error);
+        }
+
+        self.errors.push(error);
+        while self.stream.peek().map_or(false, |ch| !",}{<".contains(*ch)) {
+            self.next_char()?;
+        }
+        Ok(())
+    }
+
+    fn next_char(&mut self) -> Result<char> {
+        match self.stream.next() {
+            Some(ch) => {
+                self.position.advance(ch);
+                Ok(ch)
+            }
+            None => Err(Error::UnexpectedEof(self.position)),
         }
     }
 
-    fn expect_char_or_eof(&mut self, expected_chars: &str) -> Result<Option<char>> {
-        let ch = self.stream.next()?;
+    fn expect_char(&mut self, expected_chars: &str) -> Result<char> {
+        let position = self.position;
+        let ch = self.next_char()?;
         if expected_chars.contains(ch) {
-            Ok(Some(ch))
+            Ok(ch)
         } else {
-            Err(Error::UnexpectedChar(ch, String::from(expected_chars)))
+            Err(Error::UnexpectedChar(ch, String::from(expected_chars), position))
         }
     }
 
@@ -99,6 +364,7 @@ impl<'a> Parser<'a> {
         let ch = self.peek_char(accepted_chars);
         if ch.is_some() {
             self.stream.next();
+            self.position.advance(ch.unwrap());
         }
         ch
     }
@@ -113,17 +379,322 @@ impl<'a> Parser<'a> {
     }
 }
 
-fn count_garbage(stream: &String) -> Result<u32> {
+// offsets are absolute byte positions in the stream, garbage spans include
+// the enclosing '<' and '>'
+#[derive(Debug)]
+enum Event {
+    GroupOpen { offset: usize, depth: u32 },
+    GroupClose { start: usize, end: usize, depth: u32 },
+    Garbage { start: usize, end: usize, count: usize },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Start,
+    GroupStart,
+    ItemStart,
+    AfterItem,
+    Garbage,
+    Cancel,
+    Recover,
+    Done,
+    // input after the root group, ignored once reported
+    Trailing,
+}
+
+// push-based counterpart of Parser which accepts input in chunks of any size
+// and only keeps the offsets of currently open groups
+struct StreamParser {
+    state: State,
+    open_groups: Vec<usize>,
+    position: Position,
+    garbage_start: usize,
+    garbage_count: usize,
+    // leading bytes of a non-ASCII character outside garbage, and where it
+    // starts
+    pending: Vec<u8>,
+    pending_position: Position,
+    recover: bool,
+    errors: Vec<Error>,
+}
+
+impl StreamParser {
+    fn new(recover: bool) -> StreamParser {
+        StreamParser {
+            state: State::Start,
+            open_groups: Vec::new(),
+            position: Position::new(),
+            garbage_start: 0,
+            garbage_count: 0,
+            pending: Vec::new(),
+            pending_position: Position::new(),
+            recover: recover,
+            errors: Vec::new(),
+        }
+    }
+
+    fn feed<F>(&mut self, chunk: &[u8], handler: &mut F) -> Result<()>
+        where F: FnMut(Event)
+    {
+        for &byte in chunk {
+            self.feed_byte(byte, handler)?;
+            self.position.advance_byte(byte);
+        }
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.pending.is_empty() && (self.state == State::Done || self.state == State::Trailing) {
+            Ok(())
+        } else {
+            Err(Error::UnexpectedEof(self.position))
+        }
+    }
+
+    // garbage and skipped input are handled byte by byte, elsewhere a
+    // non-ASCII character is always an error, so its bytes are decoded
+    // first for the error to show the character
+    fn feed_byte<F>(&mut self, byte: u8, handler: &mut F) -> Result<()>
+        where F: FnMut(Event)
+    {
+        match self.state {
+            State::Garbage | State::Cancel | State::Recover | State::Trailing => {
+                let position = self.position;
+                return self.feed_char(byte as char, position, handler);
+            }
+            _ => {}
+        }
+
+        if !self.pending.is_empty() && byte & 0xc0 != 0x80 {
+            // the character was cut short
+            self.pending.clear();
+            let position = self.pending_position;
+            self.feed_char(std::char::REPLACEMENT_CHARACTER, position, handler)?;
+            return self.feed_byte(byte, handler);
+        }
+        if byte < 0x80 {
+            let position = self.position;
+            return self.feed_char(byte as char, position, handler);
+        }
+
+        if self.pending.is_empty() {
+            self.pending_position = self.position;
+        }
+        self.pending.push(byte);
+        let ch = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.chars().next().unwrap(),
+            Err(ref e) if e.error_len().is_none() => return Ok(()),
+            Err(_) => std::char::REPLACEMENT_CHARACTER,
+        };
+        self.pending.clear();
+        let position = self.pending_position;
+        self.feed_char(ch, position, handler)
+    }
+
+    fn feed_char<F>(&mut self, ch: char, position: Position, handler: &mut F) -> Result<()>
+        where F: FnMut(Event)
+    {
+        let offset = position.offset;
+        self.state = match (self.state, ch) {
+            (State::Start, '{') | (State::GroupStart, '{') | (State::ItemStart, '{') | (State::Recover, '{') => {
+                self.open_groups.push(offset);
+                handler(Event::GroupOpen { offset: offset, depth: self.open_groups.len() as u32 });
+                State::GroupStart
+            }
+            (State::GroupStart, '<') | (State::ItemStart, '<') | (State::Recover, '<') => {
+                self.garbage_start = offset;
+                self.garbage_count = 0;
+                State::Garbage
+            }
+            (State::GroupStart, '}') | (State::AfterItem, '}') | (State::Recover, '}') => {
+                let depth = self.open_groups.len() as u32;
+                let start = self.open_groups.pop().unwrap();
+                handler(Event::GroupClose { start: start, end: offset + 1, depth: depth });
+                if self.open_groups.is_empty() { State::Done } else { State::AfterItem }
+            }
+            (State::AfterItem, ',') | (State::Recover, ',') => State::ItemStart,
+            (State::Garbage, '>') => {
+                handler(Event::Garbage {
+                    start: self.garbage_start,
+                    end: offset + 1,
+                    count: self.garbage_count,
+                });
+                State::AfterItem
+            }
+            (State::Garbage, '!') => State::Cancel,
+            (State::Garbage, _) => {
+                // chars are raw bytes here, a multibyte character counts once
+                if ch as u32 & 0xc0 != 0x80 {
+                    self.garbage_count += 1;
+                }
+                State::Garbage
+            }
+            (State::Cancel, _) => State::Garbage,
+            (State::Recover, _) => State::Recover,
+            (State::Done, ch) if ch.is_whitespace() => State::Done,
+            (State::Trailing, _) => State::Trailing,
+            (state, ch) => {
+                let error = Error::UnexpectedChar(ch, String::from(Self::expected_chars(state)), position);
+                if !self.recover || state == State::Start {
+                    return Err(error);
+                }
+                self.errors.push(error);
+                if state == State::Done {
+                    self.state = State::Trailing;
+                    return Ok(());
+                }
+                // the offending char may itself be where to resynchronize,
+                // a ',' or '}' or the start of another item
+                self.state = State::Recover;
+                return self.feed_char(ch, position, handler);
+            }
+        };
+        Ok(())
+    }
+
+    fn expected_chars(state: State) -> &'static str {
+        match state {
+            State::Start => "{",
+            State::GroupStart => "{<}",
+            State::ItemStart => "{<",
+            State::AfterItem => ",}",
+            State::Garbage | State::Cancel | State::Recover | State::Done | State::Trailing => "",
+        }
+    }
+}
+
+// the errors recovered from are returned even if parsing failed later on
+fn parse_reader<R, F>(reader: &mut R, recover: bool, handler: &mut F) -> (Result<()>, Vec<Error>)
+    where R: Read, F: FnMut(Event)
+{
+    let mut parser = StreamParser::new(recover);
+    let result = feed_reader(&mut parser, reader, handler);
+    (result, parser.errors)
+}
+
+fn feed_reader<R, F>(parser: &mut StreamParser, reader: &mut R, handler: &mut F) -> Result<()>
+    where R: Read, F: FnMut(Event)
+{
+    let mut buffer = [0u8; 65536];
+
+    loop {
+        let len = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::from(e)),
+        };
+        parser.feed(&buffer[..len], handler)?;
+    }
+
+    parser.finish()
+}
+
+fn print_errors(errors: &Vec<Error>, source: Option<&str>) {
+    for error in errors {
+        match source {
+            Some(source) => eprintln!("{}", error.report(source)),
+            None => eprintln!("{}", error),
+        }
+    }
+}
+
+fn print_streamed_stats(recover: bool) {
+    let stdin = std::io::stdin();
+    let mut score = 0;
+    let mut garbage_count = 0;
+
+    let (result, errors) = parse_reader(&mut stdin.lock(), recover, &mut |event| {
+        match event {
+            Event::GroupOpen { depth, .. } => score += depth,
+            Event::GroupClose { .. } => {}
+            Event::Garbage { count, .. } => garbage_count += count,
+        }
+    });
+    print_errors(&errors, None);
+    if let Err(error) = result {
+        print_errors(&vec![error], None);
+        std::process::exit(1);
+    }
+
+    println!("score: {}", score);
+    println!("garbage: {}", garbage_count);
+}
+
+fn print_streamed_events(recover: bool) {
+    let stdin = std::io::stdin();
+
+    let (result, errors) = parse_reader(&mut stdin.lock(), recover, &mut |event| {
+        match event {
+            Event::GroupOpen { offset, depth } => println!("open {} depth {}", offset, depth),
+            Event::GroupClose { start, end, depth } => println!("close {}..{} depth {}", start, end, depth),
+            Event::Garbage { start, end, count } => println!("garbage {}..{} count {}", start, end, count),
+        }
+    });
+    print_errors(&errors, None);
+    if let Err(error) = result {
+        print_errors(&vec![error], None);
+        std::process::exit(1);
+    }
+}
+
+// the errors recovered from are returned even if parsing failed later on
+fn parse_stream(stream: &String, recover: bool) -> (Result<Node>, Vec<Error>) {
     let chars = stream.chars();
     let mut peekable = chars.peekable();
-    let mut parser = Parser::new(&mut peekable);
-    parser.parse()?;
-    Ok(parser.garbage_count)
+    let mut parser = Parser::new(&mut peekable, recover);
+    let tree = parser.parse();
+    (tree, parser.errors)
+}
+
+fn print_stats(tree: &Node) {
+    println!("score: {}", tree.group_score());
+    println!("garbage: {}", tree.garbage_count());
+
+    for (depth, num_groups) in tree.depth_histogram() {
+        println!("depth {}: {} groups", depth, num_groups);
+    }
+
+    let (largest, depth) = tree.largest_group();
+    println!("largest group: {} children at depth {}", largest.num_children(), depth);
+}
+
+fn print_garbage(tree: &Node) {
+    tree.fold((), &mut |_, node, _depth| {
+        if let Node::Garbage { .. } = *node {
+            println!("{}", node.cleaned_garbage());
+        }
+    });
 }
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let recover = args.iter().any(|arg| arg == "--recover");
+    let mode = args.iter().find(|arg| !arg.starts_with("--")).map(|arg| arg.as_str());
+
+    match mode {
+        Some("stream") => return print_streamed_stats(recover),
+        Some("events") => return print_streamed_events(recover),
+        _ => {}
+    }
+
     let mut stream = String::new();
     std::io::stdin().read_to_string(&mut stream).expect("input error");
-    let num_garbage = count_garbage(&stream).expect("parse error");
-    println!("{}", num_garbage);
+    let (tree, errors) = parse_stream(&stream, recover);
+    print_errors(&errors, Some(&stream));
+    let tree = match tree {
+        Ok(tree) => tree,
+        Err(error) => {
+            print_errors(&vec![error], Some(&stream));
+            std::process::exit(1);
+        }
+    };
+
+    match mode {
+        None => println!("{}", tree.garbage_count()),
+        Some("stats") => print_stats(&tree),
+        Some("garbage") => print_garbage(&tree),
+        Some("print") => println!("{}", tree),
+        Some(mode) => panic!("unknown mode: {}", mode),
+    }
 }